@@ -44,13 +44,37 @@ in {
             };
 
             repo = mkOption {
-              type = types.str;
-              description = "Name of the repo.";
+              type = types.nullOr types.str;
+              default = null;
+              description = ''
+                Name of the repo. Tracks items in all repos of the owner
+                if null.
+              '';
             };
 
             label = mkOption {
-              type = types.str;
-              description = "Name of the label.";
+              type = types.nullOr types.str;
+              default = null;
+              description = "Name of the label. Exactly one of label and query must be set.";
+            };
+
+            query = mkOption {
+              type = types.nullOr types.str;
+              default = null;
+              description = ''
+                Search query selecting the items to track instead of a label,
+                eg. `is:pr author:app/dependabot`.
+              '';
+            };
+
+            remote = mkOption {
+              type = types.nullOr types.str;
+              default = null;
+              description = ''
+                Git URL to fetch channel branches from for landing detection,
+                eg. a mirror or an SSH remote. Defaults to the repo on github.
+                Only applied when the state is first created, and requires repo.
+              '';
             };
 
             channels = mkOption {
@@ -64,6 +88,30 @@ in {
               '';
               default = {};
             };
+
+            namedChannels = mkOption {
+              type = types.attrsOf types.str;
+              description = ''
+                Channels that are not branches, mapped to a URL that returns
+                the commit the channel is currently at (eg.
+                https://channels.nixos.org/nixos-unstable/git-revision).
+                Targets in `channels` naming one of these check for landing
+                in that commit instead of in a branch.
+              '';
+              default = {};
+            };
+
+            sinks = mkOption {
+              type = types.attrsOf (types.attrsOf types.anything);
+              description = ''
+                Notification sinks that new history is delivered to after
+                each sync, as accepted by `label-tracker notify --sinks`.
+                These end up in the nix store, so sink URLs must not be
+                secret. An SMTP_PASSWORD for email sinks can be given in
+                the api token file.
+              '';
+              default = {};
+            };
           };
         });
         default = [];
@@ -83,6 +131,27 @@ in {
   };
 
   config = mkIf cfg.enable {
+    assertions =
+      concatLists
+      (mapAttrsToList
+        (key: args: [
+          {
+            assertion = (args.label == null) != (args.query == null);
+            message = ''
+              services.label-tracker.track.${key} must set exactly one of label
+              and query.
+            '';
+          }
+          {
+            assertion = args.repo != null || args.remote == null;
+            message = ''
+              services.label-tracker.track.${key}.remote can only be set along with
+              a repo, states tracking all repos of an owner fetch from github.
+            '';
+          }
+        ])
+        cfg.track);
+
     users.users.label-tracker = {
       isSystemUser = true;
       group = cfg.group;
@@ -102,25 +171,40 @@ in {
             (key: args: let
               name = escapeShellArg key;
               owner = escapeShellArg args.owner;
-              repo = escapeShellArg args.repo;
-              label = escapeShellArg args.label;
-              patterns =
-                escapeShellArg
-                (concatStringsSep ","
+              tracked =
+                if args.query != null
+                then "--query ${escapeShellArg args.query}"
+                else escapeShellArg args.label;
+              remote =
+                optionalString (args.remote != null)
+                "--remote ${escapeShellArg args.remote}";
+              init =
+                if args.repo == null
+                then "init-owner states/${name} ${owner} ${tracked}"
+                else "init states/${name} ${owner} ${escapeShellArg args.repo} ${tracked} ${remote}";
+              landing =
+                optionalString (args.channels != {})
+                "-l states/${name}.git -P ${pkgs.writeText "label-tracker-patterns.json"
+                  (builtins.toJSON args.channels)} ${concatStringsSep " "
                   (mapAttrsToList
-                    (base: targets: "${base}:${concatStringsSep " " targets}")
-                    args.channels));
+                    (channel: url: "-c ${escapeShellArg "${channel}=${url}"}")
+                    args.namedChannels)}";
             in ''
               (
                 umask 0077
                 if ! [ -e states/${name} ]; then
                   mkdir -p states
-                  label-tracker init states/${name} ${owner} ${repo} ${label}
+                  label-tracker ${init}
                 fi
                 label-tracker sync-issues states/${name}
-                label-tracker sync-prs states/${name} \
-                  -l states/${name}.git \
-                  -p ${patterns}
+                label-tracker sync-prs states/${name} ${landing}
+                ${optionalString (args.sinks != {}) ''
+                  # sinks resume where they failed on the next run, so a
+                  # sink being down must not keep feeds from being written.
+                  label-tracker notify states/${name} -s ${pkgs.writeText "label-tracker-sinks.json"
+                    (builtins.toJSON args.sinks)} \
+                    || echo "notifying sinks of ${name} failed" >&2
+                ''}
               )
               (
                 umask 0027
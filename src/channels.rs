@@ -0,0 +1,368 @@
+use std::{
+    borrow::ToOwned,
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    fs::File,
+    io::BufReader,
+    path::Path,
+    str::FromStr,
+};
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde_json::from_reader;
+
+use crate::full_matchable_regex::FullMatchableRegex;
+
+#[derive(Debug, Default)]
+pub struct ChannelPatterns {
+    patterns: Vec<(Regex, Vec<String>)>,
+}
+
+impl ChannelPatterns {
+    pub fn new(patterns: Vec<(Regex, Vec<String>)>) -> Result<Self> {
+        for (target_regex, channel_globs) in &patterns {
+            for channel_glob in channel_globs {
+                if let Some(name) = undefined_capture(target_regex, channel_glob) {
+                    bail!(
+                        "channel `{channel_glob}` for target `{target_regex}` refers to \
+                         undefined capture group `{name}`{}",
+                        if name.starts_with(|c: char| c.is_ascii_digit()) {
+                            " (use ${N} to separate group numbers from text)"
+                        } else {
+                            ""
+                        }
+                    );
+                }
+            }
+        }
+        Ok(ChannelPatterns { patterns })
+    }
+
+    /// Reads patterns from a JSON file mapping target regexes to lists of
+    /// channel globs, eg. `{ "release-(.*)": [ "nixos-$1", "nixos-$1-small" ] }`.
+    pub fn from_file(file: impl AsRef<Path>) -> Result<Self> {
+        let file = file.as_ref();
+        let patterns: BTreeMap<String, Vec<String>> = from_reader(BufReader::new(
+            File::open(file).with_context(|| format!("failed to open {}", file.display()))?,
+        ))
+        .with_context(|| format!("failed to parse patterns in {}", file.display()))?;
+        Self::new(
+            patterns
+                .into_iter()
+                .map(|(target_regex, channel_globs)| {
+                    Ok((Regex::new(&target_regex)?, channel_globs))
+                })
+                .collect::<Result<_>>()?,
+        )
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    pub fn find_channels(&self, target: &str) -> BTreeSet<String> {
+        self.patterns
+            .iter()
+            .filter(|(target_regex, _)| target_regex.is_full_match(target))
+            .flat_map(|(target_regex, channel_globs)| {
+                channel_globs
+                    .iter()
+                    .map(|channel_glob| target_regex.replace(target, channel_glob).to_string())
+            })
+            .collect()
+    }
+}
+
+impl FromStr for ChannelPatterns {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let patterns = s
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| match s.split_once(':') {
+                Some((target_regex, channel_globs)) => Ok((
+                    Regex::new(target_regex)?,
+                    channel_globs
+                        .split_whitespace()
+                        .map(ToOwned::to_owned)
+                        .collect::<Vec<_>>(),
+                )),
+                None => bail!("invalid channel pattern `{s}`"),
+            })
+            .collect::<Result<_>>()?;
+        Self::new(patterns)
+    }
+}
+
+/// A channel that is not a branch, but whose current commit is published at
+/// a URL. nixos channels do this with their `git-revision` file.
+#[derive(Debug)]
+pub struct NamedChannel {
+    pub name: String,
+    pub url: String,
+}
+
+impl NamedChannel {
+    pub fn resolve(&self) -> Result<String> {
+        let commit = reqwest::blocking::get(&self.url)
+            .and_then(reqwest::blocking::Response::error_for_status)
+            .and_then(reqwest::blocking::Response::text)
+            .with_context(|| format!("failed to resolve channel {}", self.name))?;
+        let commit = commit.trim();
+        if commit.len() != 40 || !commit.bytes().all(|b| b.is_ascii_hexdigit()) {
+            bail!(
+                "channel {} resolved to invalid commit `{commit}`",
+                self.name
+            );
+        }
+        Ok(commit.to_owned())
+    }
+}
+
+impl FromStr for NamedChannel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((name, url)) => Ok(NamedChannel {
+                name: name.trim().to_owned(),
+                url: url.trim().to_owned(),
+            }),
+            None => bail!("invalid channel definition `{s}`"),
+        }
+    }
+}
+
+/// A shell-style glob selecting channels by name. Supports `*`, `?`, `\`
+/// escapes and bracket classes like `git branch --list` does.
+#[derive(Debug)]
+pub struct ChannelGlob {
+    glob: String,
+    regex: Regex,
+}
+
+impl ChannelGlob {
+    pub fn matches(&self, channel: &str) -> bool {
+        self.regex.is_full_match(channel)
+    }
+}
+
+impl FromStr for ChannelGlob {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut regex = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '*' => regex.push_str(".*"),
+                '?' => regex.push('.'),
+                '\\' => match chars.next() {
+                    Some(c) => regex.push_str(&regex::escape(&c.to_string())),
+                    None => bail!("glob `{s}` ends in an escape"),
+                },
+                '[' => {
+                    regex.push('[');
+                    if chars.next_if(|&c| c == '!' || c == '^').is_some() {
+                        regex.push('^');
+                    }
+                    // a `]` right after the opening bracket is part of the
+                    // class, as in `[]a]`.
+                    let mut first = true;
+                    loop {
+                        match chars.next() {
+                            None => bail!("unterminated bracket class in glob `{s}`"),
+                            Some(']') if !first => break,
+                            Some('[') if chars.peek() == Some(&':') => {
+                                // character classes like `[:digit:]` mean
+                                // the same to git and regex.
+                                regex.push('[');
+                                for c in chars.by_ref() {
+                                    regex.push(c);
+                                    if regex.ends_with(":]") {
+                                        break;
+                                    }
+                                }
+                            }
+                            Some('\\') => match chars.next() {
+                                Some(c) => regex.push_str(&regex::escape(&c.to_string())),
+                                None => bail!("glob `{s}` ends in an escape"),
+                            },
+                            Some('-') => regex.push('-'),
+                            Some(c) => regex.push_str(&regex::escape(&c.to_string())),
+                        }
+                        first = false;
+                    }
+                    regex.push(']');
+                }
+                c => regex.push_str(&regex::escape(&c.to_string())),
+            }
+        }
+        Ok(ChannelGlob {
+            glob: s.to_owned(),
+            regex: Regex::new(&regex).with_context(|| format!("invalid glob `{s}`"))?,
+        })
+    }
+}
+
+impl fmt::Display for ChannelGlob {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.glob)
+    }
+}
+
+/// Finds capture references in `replacement` to groups that do not exist in
+/// `regex`. References are parsed like `Regex::replace` does, which notably
+/// reads `$1a` as a reference to a group named `1a`.
+fn undefined_capture<'a>(regex: &Regex, replacement: &'a str) -> Option<&'a str> {
+    let mut rest = replacement;
+    while let Some(at) = rest.find('$') {
+        rest = &rest[at + 1..];
+        let name = if let Some(braced) = rest.strip_prefix('{') {
+            match braced.split_once('}') {
+                Some((name, after)) => {
+                    rest = after;
+                    name
+                }
+                None => break,
+            }
+        } else {
+            let len = rest
+                .find(|c: char| !(c == '_' || c.is_ascii_alphanumeric()))
+                .unwrap_or(rest.len());
+            let name = &rest[..len];
+            // `$$` is an escaped `$`, anything else is taken literally.
+            rest = &rest[len.max(usize::from(rest.starts_with('$')))..];
+            name
+        };
+        if name.is_empty() {
+            continue;
+        }
+        let exists = match name.parse::<usize>() {
+            Ok(index) => index < regex.captures_len(),
+            Err(_) => regex.capture_names().flatten().any(|n| n == name),
+        };
+        if !exists {
+            return Some(name);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf, thread};
+
+    use tiny_http::{Response, Server};
+
+    use regex::Regex;
+
+    use super::{undefined_capture, ChannelGlob, ChannelPatterns, NamedChannel};
+
+    /// Serves the files in `dir` until the test process exits, returning
+    /// the base URL.
+    fn serve_dir(dir: PathBuf) -> String {
+        let server = Server::http("127.0.0.1:0").expect("failed to start server");
+        let url = format!("http://{}", server.server_addr());
+        thread::spawn(move || {
+            for request in server.incoming_requests() {
+                let file = dir.join(request.url().trim_start_matches('/'));
+                let _ = match fs::read(file) {
+                    Ok(body) => request.respond(Response::from_data(body)),
+                    Err(_) => request.respond(Response::empty(404)),
+                };
+            }
+        });
+        url
+    }
+
+    fn resolve(url: String) -> anyhow::Result<String> {
+        NamedChannel {
+            name: "nixos-unstable".to_owned(),
+            url,
+        }
+        .resolve()
+    }
+
+    #[test]
+    fn resolve_named_channel() {
+        let dir = tempfile::tempdir().unwrap();
+        let commit = "0123456789abcdef0123456789abcdef01234567";
+        fs::write(dir.path().join("git-revision"), format!("{commit}\n")).unwrap();
+        fs::write(dir.path().join("malformed"), "<html>not a commit</html>").unwrap();
+        let url = serve_dir(dir.path().to_owned());
+
+        assert_eq!(resolve(format!("{url}/git-revision")).unwrap(), commit);
+        let err = resolve(format!("{url}/malformed")).unwrap_err();
+        assert!(err.to_string().contains("invalid commit"), "{err}");
+        let err = resolve(format!("{url}/missing")).unwrap_err();
+        assert!(err.to_string().contains("failed to resolve"), "{err}");
+    }
+
+    fn glob_matches(glob: &str, channel: &str) -> bool {
+        glob.parse::<ChannelGlob>().unwrap().matches(channel)
+    }
+
+    #[test]
+    fn channel_globs() {
+        assert!(glob_matches("nixos-*", "nixos-23.11-small"));
+        assert!(glob_matches("release-2?.11", "release-23.11"));
+        assert!(!glob_matches("release-2?.11", "release-2.11"));
+        assert!(glob_matches("release-[0-9]*", "release-23.11"));
+        assert!(!glob_matches("release-[0-9]*", "release-staging"));
+        assert!(glob_matches("release-[!0-9]*", "release-staging"));
+        assert!(glob_matches("release-[^0-9]*", "release-staging"));
+        assert!(glob_matches("[]a]", "]"));
+        assert!(glob_matches("v[[:digit:]]", "v1"));
+        assert!(glob_matches(r"a\*", "a*"));
+        assert!(!glob_matches(r"a\*", "ab"));
+        assert!(glob_matches("a.b", "a.b"));
+        assert!(!glob_matches("a.b", "axb"));
+        assert!("release-[0-9".parse::<ChannelGlob>().is_err());
+        assert!(r"a\".parse::<ChannelGlob>().is_err());
+    }
+
+    #[test]
+    fn undefined_captures() {
+        let numbered = Regex::new("release-(.*)").unwrap();
+        assert_eq!(undefined_capture(&numbered, "nixos-$1"), None);
+        assert_eq!(undefined_capture(&numbered, "nixos-$0"), None);
+        assert_eq!(undefined_capture(&numbered, "nixos-$2"), Some("2"));
+        // `Regex::replace` reads `$1a` as the group named `1a`.
+        assert_eq!(undefined_capture(&numbered, "$1a"), Some("1a"));
+        assert_eq!(undefined_capture(&numbered, "${1}a"), None);
+        assert_eq!(undefined_capture(&numbered, "${2}a"), Some("2"));
+        assert_eq!(undefined_capture(&numbered, "$$2"), None);
+        assert_eq!(undefined_capture(&numbered, "cost-$"), None);
+        assert_eq!(undefined_capture(&numbered, "$-$1"), None);
+        assert_eq!(undefined_capture(&numbered, "${2"), None);
+
+        let named = Regex::new("release-(?P<version>.*)").unwrap();
+        assert_eq!(undefined_capture(&named, "nixos-$version"), None);
+        assert_eq!(undefined_capture(&named, "nixos-${version}-small"), None);
+        assert_eq!(undefined_capture(&named, "nixos-$version-small"), None);
+        assert_eq!(
+            undefined_capture(&named, "nixos-$versions"),
+            Some("versions")
+        );
+        assert_eq!(undefined_capture(&named, "nixos-$1"), None);
+    }
+
+    #[test]
+    fn patterns_reject_undefined_captures() {
+        let err = "release-(.*):nixos-$1small"
+            .parse::<ChannelPatterns>()
+            .unwrap_err();
+        assert!(err.to_string().contains("use ${N}"), "{err}");
+        let err = "release-(.*):nixos-$v"
+            .parse::<ChannelPatterns>()
+            .unwrap_err();
+        assert!(!err.to_string().contains("use ${N}"), "{err}");
+        assert!("release-(.*):nixos-${1}small"
+            .parse::<ChannelPatterns>()
+            .is_ok());
+    }
+}
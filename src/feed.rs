@@ -0,0 +1,892 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use ammonia::{Url, UrlRelative};
+use anyhow::{Context, Result};
+use chrono::{Duration, Utc};
+use minijinja::{AutoEscape, Environment};
+use rss::{
+    extension::{
+        atom::{AtomExtension, Link},
+        dublincore::DublinCoreExtension,
+        Extension,
+    },
+    Category, Channel, ChannelBuilder, Guid, Item, ItemBuilder,
+};
+use serde::Serialize;
+use tempfile::NamedTempFile;
+
+use crate::{
+    channels::ChannelGlob,
+    state::{
+        Action, CheckState, Comment, DateTime, Discussion, DiscussionAction, Issue, IssueAction,
+        ItemRef, PullAction, PullRequest, State,
+    },
+};
+
+/// Describes what a state tracks, for the default feed titles.
+macro_rules! tracked {
+    () => {
+        "{% if state.query %}matching `{{ state.query }}'\
+         {% else %}labeled `{{ state.label }}'{% endif %} \
+         in {{ state.owner }}{% if state.repo %}/{{ state.repo }}{% endif %}"
+    };
+}
+
+pub const DEFAULT_ISSUES_TITLE: &str = concat!("Issues ", tracked!());
+pub const DEFAULT_PRS_TITLE: &str = concat!(
+    "Pull requests ",
+    tracked!(),
+    "{% if channel %} landing in {{ channel }}{% endif %}"
+);
+pub const DEFAULT_DISCUSSIONS_TITLE: &str = concat!("Discussions ", tracked!());
+pub const DEFAULT_ITEM_TITLE: &str =
+    "{{ tag }} {% if not state.repo %}{{ item.repo }}: {% endif %}{{ item.title }}";
+pub const DEFAULT_ITEM_CONTENT: &str = "\
+    {% if previous_title %}<p>Renamed from “{{ previous_title }}”</p>{% endif %}\
+    {% if closer %}<p>Closed by <a href=\"{{ closer.url }}\">{{ closer.title or closer.url }}</a>\
+    {% if closer.landed_in %}, landed in {{ closer.landed_in|join(\", \") }}{% endif %}</p>{% endif %}\
+    {% if action == \"Answered\" and item.answer %}\
+    <p><a href=\"{{ item.answer.url }}\">{{ item.answer.author }}</a> answered:</p>{{ item.answer.body }}\
+    {% elif comments %}{% for comment in comments %}\
+    <p><a href=\"{{ comment.url }}\">{{ comment.author }}</a> commented:</p>{{ comment.body }}\
+    {% endfor %}{% else %}{{ item.body }}{% endif %}\
+    {% if item.closes %}<p>Closes {% for issue in item.closes %}\
+    <a href=\"{{ issue.url }}\">#{{ issue.number }}</a>{% if not loop.last %}, {% endif %}\
+    {% endfor %}</p>{% endif %}";
+
+const FH_NAMESPACE: &str = "http://purl.org/syndication/history/1.0";
+
+/// Settings shared by all feeds.
+pub struct FeedOptions {
+    pub age_hours: u32,
+    pub actions: ActionFilter,
+    pub templates: Templates,
+    pub sanitize: bool,
+    /// Length limit of item contents in bytes. Longer contents are cut short
+    /// and sanitized, and link to the full item.
+    pub max_body_length: Option<usize>,
+    pub max_items: Option<usize>,
+    pub filter: ItemFilter,
+    /// How long readers may cache the feed, in minutes.
+    pub ttl: Option<u32>,
+}
+
+impl FeedOptions {
+    fn clean_body(&self, state: &State, repo: &str, body: String, link: &str) -> Result<String> {
+        let base = Url::parse(&format!("https://github.com/{}/{}/", state.owner, repo))?;
+        let sanitize = |html: &str| {
+            ammonia::Builder::default()
+                .url_relative(UrlRelative::RewriteWithBase(base.clone()))
+                .clean(html)
+                .to_string()
+        };
+
+        let body = if self.sanitize { sanitize(&body) } else { body };
+        match self.max_body_length {
+            Some(max) if body.len() > max => {
+                let mut cut = (0..=max)
+                    .rev()
+                    .find(|&at| body.is_char_boundary(at))
+                    .unwrap_or(0);
+                // don't leave half a tag behind, the rest is fixed up by the
+                // sanitizer closing all open elements.
+                if let Some(open) = body[..cut].rfind('<') {
+                    if !body[open..cut].contains('>') {
+                        cut = open;
+                    }
+                }
+                Ok(format!(
+                    "{}<p><a href=\"{}\">Read more…</a></p>",
+                    sanitize(&body[..cut]),
+                    ammonia::clean_text(link),
+                ))
+            }
+            _ => Ok(body),
+        }
+    }
+}
+
+/// Selects history entries by their kind of action.
+pub struct ActionFilter {
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl ActionFilter {
+    pub fn new<A: Action>(include: &[String], exclude: &[String]) -> Result<Self> {
+        let check = |kinds: &[String]| {
+            kinds
+                .iter()
+                .map(
+                    |kind| match A::KINDS.iter().find(|k| k.eq_ignore_ascii_case(kind)) {
+                        Some(k) => Ok((*k).to_owned()),
+                        None => bail!(
+                            "unknown action `{kind}`, expected one of {}",
+                            A::KINDS.join(", ")
+                        ),
+                    },
+                )
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(ActionFilter {
+            include: check(include)?,
+            exclude: check(exclude)?,
+        })
+    }
+
+    fn allows(&self, how: &impl Action) -> bool {
+        let kind = how.kind();
+        (self.include.is_empty() || self.include.iter().any(|k| k == kind))
+            && !self.exclude.iter().any(|k| k == kind)
+    }
+}
+
+/// Selects history entries by properties of the item they refer to. Each
+/// non-empty list must contain one of the item's values. Items can change
+/// after they were archived, so filtering archived feeds on them may move
+/// entries between pages.
+#[derive(Default)]
+pub struct ItemFilter {
+    pub authors: Vec<String>,
+    pub milestones: Vec<String>,
+    pub labels: Vec<String>,
+}
+
+impl ItemFilter {
+    fn allows(&self, item: &impl FeedItem) -> bool {
+        let any = |wanted: &[String], have: &[&str]| {
+            wanted.is_empty() || wanted.iter().any(|w| have.contains(&w.as_str()))
+        };
+        any(&self.authors, &Vec::from_iter(item.author()))
+            && any(&self.milestones, &Vec::from_iter(item.milestone()))
+            && any(&self.labels, &item.labels())
+    }
+}
+
+/// Templates for the feed title and the title and content of items.
+///
+/// The feed title can refer to `state` (with `owner`, `repo` unless it tracks
+/// all repos of the owner, and `label` or `query`) and `channel`. Items can additionally
+/// refer to the tracked `item` with all its fields, its `id`, the `action` and
+/// the `channels` it affected, the new `comments` of comment events, the
+/// `previous_title` of renaming edits, the `closer` PR of issues closed by one
+/// (with its `url`, `title` if known and the channels it `landed_in` if
+/// tracked), the default `tag` and the time it `changed`.
+pub struct Templates {
+    env: Environment<'static>,
+}
+
+impl Templates {
+    /// Templates are given as template text, or as `@path` to read them from
+    /// a file.
+    pub fn new(title: &str, item_title: &str, item_content: &str) -> Result<Self> {
+        let mut env = Environment::new();
+        // the rss writer does all escaping, and bodies are html to begin with.
+        env.set_auto_escape_callback(|_| AutoEscape::None);
+        for (name, source) in [
+            ("title", title),
+            ("item_title", item_title),
+            ("item_content", item_content),
+        ] {
+            let source = match source.strip_prefix('@') {
+                Some(file) => fs::read_to_string(file)
+                    .with_context(|| format!("failed to read {name} template {file}"))?,
+                None => source.to_owned(),
+            };
+            env.add_template_owned(name, source)
+                .with_context(|| format!("invalid {name} template"))?;
+        }
+        Ok(Templates { env })
+    }
+
+    fn render(&self, name: &str, ctx: impl Serialize) -> Result<String> {
+        self.env
+            .get_template(name)?
+            .render(ctx)
+            .with_context(|| format!("failed to render {name} template"))
+    }
+}
+
+#[derive(Serialize)]
+struct StateContext<'a> {
+    owner: &'a str,
+    repo: Option<&'a str>,
+    label: Option<&'a str>,
+    query: Option<&'a str>,
+}
+
+impl<'a> From<&'a State> for StateContext<'a> {
+    fn from(state: &'a State) -> Self {
+        StateContext {
+            owner: &state.owner,
+            repo: state.repo.as_deref(),
+            label: state.label.as_deref(),
+            query: state.query.as_deref(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct TitleContext<'a> {
+    state: StateContext<'a>,
+    channel: Option<String>,
+}
+
+#[derive(Serialize)]
+struct CloserContext<'a> {
+    url: &'a str,
+    title: Option<&'a str>,
+    landed_in: Option<&'a BTreeSet<String>>,
+}
+
+#[derive(Serialize)]
+struct ItemContext<'a, V> {
+    state: StateContext<'a>,
+    id: &'a str,
+    item: &'a V,
+    action: &'static str,
+    channels: Option<&'a [String]>,
+    comments: &'a [Comment],
+    previous_title: Option<&'a str>,
+    closer: Option<CloserContext<'a>>,
+    tag: &'a str,
+    changed: String,
+}
+
+/// The comments summarized by a comment event at `changed`, as far as they
+/// are still among the latest comments of `item`.
+fn new_comments<'a>(
+    item: &'a impl FeedItem,
+    how: &impl Action,
+    changed: DateTime,
+) -> &'a [Comment] {
+    let Some(count) = how.new_comments() else {
+        return &[];
+    };
+    let latest = item.latest_comments();
+    let end = latest.partition_point(|c| c.created_at <= changed);
+    let count = usize::try_from(count).unwrap_or(usize::MAX).min(end);
+    &latest[end - count..end]
+}
+
+/// Things that history entries can refer to.
+pub trait FeedItem: Serialize {
+    fn url(&self) -> &str;
+    fn repo(&self) -> Option<&str>;
+    fn author(&self) -> Option<&str>;
+    fn milestone(&self) -> Option<&str>;
+    fn labels(&self) -> Vec<&str>;
+    fn latest_comments(&self) -> &[Comment];
+
+    fn closed_by(&self) -> Option<&ItemRef> {
+        None
+    }
+
+    /// Categories of the item besides the kind of action.
+    fn categories(&self) -> Vec<&str> {
+        vec![]
+    }
+}
+
+impl FeedItem for Issue {
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn repo(&self) -> Option<&str> {
+        self.repo.as_deref()
+    }
+
+    fn author(&self) -> Option<&str> {
+        self.author.as_deref()
+    }
+
+    fn milestone(&self) -> Option<&str> {
+        self.milestone.as_deref()
+    }
+
+    fn labels(&self) -> Vec<&str> {
+        self.labels.iter().map(String::as_str).collect()
+    }
+
+    fn latest_comments(&self) -> &[Comment] {
+        &self.latest_comments
+    }
+
+    fn closed_by(&self) -> Option<&ItemRef> {
+        self.closed_by.as_ref()
+    }
+}
+
+impl FeedItem for PullRequest {
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn repo(&self) -> Option<&str> {
+        self.repo.as_deref()
+    }
+
+    fn author(&self) -> Option<&str> {
+        self.author.as_deref()
+    }
+
+    fn milestone(&self) -> Option<&str> {
+        self.milestone.as_deref()
+    }
+
+    fn labels(&self) -> Vec<&str> {
+        self.labels.iter().map(String::as_str).collect()
+    }
+
+    fn latest_comments(&self) -> &[Comment] {
+        &self.latest_comments
+    }
+
+    fn categories(&self) -> Vec<&str> {
+        vec![&self.base_ref]
+    }
+}
+
+impl FeedItem for Discussion {
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn repo(&self) -> Option<&str> {
+        self.repo.as_deref()
+    }
+
+    fn author(&self) -> Option<&str> {
+        self.author.as_deref()
+    }
+
+    fn milestone(&self) -> Option<&str> {
+        None
+    }
+
+    fn labels(&self) -> Vec<&str> {
+        self.labels.iter().map(String::as_str).collect()
+    }
+
+    fn latest_comments(&self) -> &[Comment] {
+        &[]
+    }
+
+    fn categories(&self) -> Vec<&str> {
+        vec![&self.category]
+    }
+}
+
+/// Where a feed is published.
+#[derive(Default)]
+pub struct FeedLocation {
+    /// URL of the feed itself.
+    pub url: Option<String>,
+    pub archive: Option<Archive>,
+}
+
+impl FeedLocation {
+    /// The location of a per-channel feed, with `self` locating the directory
+    /// of all per-channel feeds.
+    pub fn for_channel(&self, channel: &str) -> FeedLocation {
+        FeedLocation {
+            url: self
+                .url
+                .as_ref()
+                .map(|url| format!("{}/{channel}.xml", url.trim_end_matches('/'))),
+            archive: self.archive.as_ref().map(|archive| Archive {
+                dir: archive.dir.join(channel),
+                url: format!("{}/{channel}", archive.url.trim_end_matches('/')),
+                page_size: archive.page_size,
+            }),
+        }
+    }
+}
+
+/// An RFC 5005 archive of a feed, kept as a directory of pages with a fixed
+/// number of history entries each. Pages are numbered from the oldest entry,
+/// so the page size must not change once pages were written.
+pub struct Archive {
+    pub dir: PathBuf,
+    /// URL the archive directory is published at.
+    pub url: String,
+    pub page_size: usize,
+}
+
+impl Archive {
+    fn page_file(&self, page: usize) -> PathBuf {
+        self.dir.join(format!("page-{}.xml", page + 1))
+    }
+
+    fn page_url(&self, page: usize) -> String {
+        format!("{}/page-{}.xml", self.url.trim_end_matches('/'), page + 1)
+    }
+}
+
+fn link(rel: &str, href: impl Into<String>) -> Link {
+    Link {
+        href: href.into(),
+        rel: rel.to_owned(),
+        ..Link::default()
+    }
+}
+
+/// Turns the history of one kind of item into feeds.
+struct FeedBuilder<'a, V, A> {
+    state: &'a State,
+    items: &'a BTreeMap<String, V>,
+    options: &'a FeedOptions,
+    channel: Option<&'a ChannelGlob>,
+    location: &'a FeedLocation,
+    tag: &'a dyn Fn(&V, &A) -> String,
+    // backwards compat of GUIDs requires this. we need either a different ID format
+    // or an id suffix to give landing events unique ids in all cases, and the suffix
+    // is easier for now
+    id_suffix: &'a dyn Fn(&A) -> String,
+}
+
+impl<'a, V: FeedItem, A: Action> FeedBuilder<'a, V, A> {
+    fn format_entries(&self, mut entries: Vec<&(DateTime, String, A)>) -> Result<Vec<Item>> {
+        // history is kept in the order changes were found, which is not the order
+        // they happened in if landings were detected late.
+        entries.sort_by_key(|&(changed, _, _)| std::cmp::Reverse(*changed));
+
+        entries
+            .into_iter()
+            .map(|(changed, id, how)| {
+                let Some(entry) = self.items.get(id.as_str()) else {
+                    panic!("database is corrupted (dangling key {id})")
+                };
+                let ctx = ItemContext {
+                    state: self.state.into(),
+                    id,
+                    item: entry,
+                    action: how.kind(),
+                    channels: how.channels(),
+                    tag: &(self.tag)(entry, how),
+                    comments: new_comments(entry, how, *changed),
+                    previous_title: how.previous_title(),
+                    closer: self.closer(entry, how),
+                    changed: changed.to_rfc3339(),
+                };
+                let templates = &self.options.templates;
+                let content = templates.render("item_content", &ctx)?;
+                let categories = [how.kind()]
+                    .into_iter()
+                    .chain(entry.categories())
+                    .map(|name| Category {
+                        name: name.to_owned(),
+                        domain: None,
+                    })
+                    .collect::<Vec<_>>();
+                Ok(ItemBuilder::default()
+                    .title(Some(templates.render("item_title", &ctx)?))
+                    .link(Some(entry.url().to_string()))
+                    .categories(categories)
+                    .dublin_core_ext(entry.author().map(|author| DublinCoreExtension {
+                        creators: vec![author.to_owned()],
+                        ..DublinCoreExtension::default()
+                    }))
+                    .guid(Some(Guid {
+                        value: format!("{}/{}{}", changed.to_rfc3339(), id, (self.id_suffix)(how)),
+                        permalink: false,
+                    }))
+                    .pub_date(Some(changed.to_rfc2822()))
+                    .content(Some(
+                        self.options.clean_body(
+                            self.state,
+                            entry
+                                .repo()
+                                .or(self.state.repo.as_deref())
+                                .unwrap_or_default(),
+                            content,
+                            entry.url(),
+                        )?,
+                    ))
+                    .build())
+            })
+            .collect()
+    }
+
+    /// Describes the PR that closed `item` in a closing event. Landings are
+    /// only known if the PR is tracked as well.
+    fn closer(&self, item: &'a V, how: &'a A) -> Option<CloserContext<'a>> {
+        let url = how.closer()?;
+        let tracked = self.state.pull_requests.values().find(|pr| pr.url == url);
+        let title = match tracked {
+            Some(pr) => Some(pr.title.as_str()),
+            None => item
+                .closed_by()
+                .filter(|pr| pr.url == url)
+                .map(|pr| pr.title.as_str()),
+        };
+        Some(CloserContext {
+            url,
+            title,
+            landed_in: tracked.map(|pr| &pr.landed_in),
+        })
+    }
+
+    fn new_channel(
+        &self,
+        entries: Vec<Item>,
+        self_url: Option<&str>,
+        mut links: Vec<Link>,
+    ) -> Result<Channel> {
+        let title = self.options.templates.render(
+            "title",
+            TitleContext {
+                state: self.state.into(),
+                channel: self.channel.map(ToString::to_string),
+            },
+        )?;
+        let label_url = match (&self.state.repo, &self.state.label, &self.state.query) {
+            (Some(repo), Some(label), None) => {
+                let mut url = Url::parse("https://github.com/")?;
+                url.path_segments_mut()
+                    .map_err(|()| anyhow!("cannot build label url"))?
+                    .extend([&self.state.owner, repo, "labels", label]);
+                url
+            }
+            _ => Url::parse_with_params(
+                "https://github.com/search",
+                [("q", self.state.search_filter())],
+            )?,
+        };
+        links.extend(self_url.map(|url| link("self", url)));
+
+        Ok(ChannelBuilder::default()
+            .description(title.clone())
+            .title(title)
+            .link(label_url.to_string())
+            .last_build_date(Some(Utc::now().to_rfc2822()))
+            .ttl(self.options.ttl.map(|ttl| ttl.to_string()))
+            .atom_ext((!links.is_empty()).then_some(AtomExtension { links }))
+            .items(entries)
+            .build())
+    }
+
+    /// Writes all complete pages of `history` to `archive` that are not written
+    /// yet, and returns the number of pages. If any are written, the newest
+    /// page before them is rewritten to link to its successor.
+    ///
+    /// Pages are cut from the unfiltered history and filtered individually,
+    /// so changing filters never moves entries between published pages.
+    fn write_archive(&self, archive: &Archive, history: &[(DateTime, String, A)]) -> Result<usize> {
+        let pages = history.len() / archive.page_size;
+        let first_missing = (0..pages)
+            .find(|&page| !archive.page_file(page).exists())
+            .unwrap_or(pages);
+        if first_missing == pages {
+            return Ok(pages);
+        }
+        fs::create_dir_all(&archive.dir)
+            .with_context(|| format!("failed to create {}", archive.dir.display()))?;
+        for page in first_missing.saturating_sub(1)..pages {
+            let start = page * archive.page_size;
+            let items = self.format_entries(
+                history[start..start + archive.page_size]
+                    .iter()
+                    .filter(|entry| self.allows(entry))
+                    .collect(),
+            )?;
+            let mut links = self
+                .location
+                .url
+                .iter()
+                .map(|url| link("current", url))
+                .collect::<Vec<_>>();
+            if page > 0 {
+                links.push(link("prev-archive", archive.page_url(page - 1)));
+            }
+            if page + 1 < pages {
+                links.push(link("next-archive", archive.page_url(page + 1)));
+            }
+            let page_url = archive.page_url(page);
+            let mut channel = self.new_channel(items, Some(&page_url), links)?;
+            channel
+                .namespaces
+                .insert("fh".to_owned(), FH_NAMESPACE.to_owned());
+            channel.extensions.insert(
+                "fh".to_owned(),
+                BTreeMap::from([(
+                    "archive".to_owned(),
+                    vec![Extension {
+                        name: "fh:archive".to_owned(),
+                        ..Extension::default()
+                    }],
+                )]),
+            );
+            write_feed(Some(archive.page_file(page)), &channel)?;
+        }
+        Ok(pages)
+    }
+
+    fn allows(&self, (_, id, how): &(DateTime, String, A)) -> bool {
+        self.options.actions.allows(how)
+            && self
+                .items
+                .get(id)
+                .map_or(true, |item| self.options.filter.allows(item))
+    }
+
+    fn build(&self, history: &[(DateTime, String, A)]) -> Result<Channel> {
+        let mut links = vec![];
+        let mut archived = history.len();
+        if let Some(archive) = &self.location.archive {
+            let pages = self.write_archive(archive, history)?;
+            archived = pages * archive.page_size;
+            if pages > 0 {
+                links.push(link("prev-archive", archive.page_url(pages - 1)));
+            }
+        }
+
+        // entries not archived yet must stay in the feed, otherwise readers
+        // catching up through the archive could never see them.
+        let unarchived = history[archived..]
+            .iter()
+            .filter(|entry| self.allows(entry))
+            .collect::<Vec<_>>();
+        let since = Utc::now() - Duration::hours(self.options.age_hours.into());
+        let mut recent = history[..archived]
+            .iter()
+            .filter(|entry| entry.0 >= since && self.allows(entry))
+            .collect::<Vec<_>>();
+        recent.sort_by_key(|entry| std::cmp::Reverse(entry.0));
+        if let Some(max) = self.options.max_items {
+            recent.truncate(max.saturating_sub(unarchived.len()));
+        }
+        recent.extend(unarchived);
+
+        let items = self.format_entries(recent)?;
+        self.new_channel(items, self.location.url.as_deref(), links)
+    }
+
+    fn notifications(&self, history: &[(DateTime, String, A)]) -> Result<Vec<Option<Item>>> {
+        history
+            .iter()
+            .map(|entry| {
+                if self.allows(entry) {
+                    Ok(self.format_entries(vec![entry])?.pop())
+                } else {
+                    Ok(None)
+                }
+            })
+            .collect()
+    }
+}
+
+fn issues_builder<'a>(
+    state: &'a State,
+    options: &'a FeedOptions,
+    location: &'a FeedLocation,
+) -> FeedBuilder<'a, Issue, IssueAction> {
+    FeedBuilder {
+        state,
+        items: &state.issues,
+        options,
+        channel: None,
+        location,
+        tag: &|_, how| {
+            match how {
+                IssueAction::New => "[NEW]",
+                IssueAction::Closed => "[CLOSED]",
+                IssueAction::NewClosed => "[NEW][CLOSED]",
+                IssueAction::Commented(_) => "[COMMENTED]",
+                IssueAction::Edited(_) => "[EDITED]",
+                IssueAction::ClosedBy(pr) => {
+                    return format!("[CLOSED-BY](#{})", pr.rsplit('/').next().unwrap_or(pr));
+                }
+                IssueAction::FixedIn(_, chans) => {
+                    return format!("[FIXED-IN]({})", chans.join(" "))
+                }
+            }
+            .to_owned()
+        },
+        id_suffix: &|how| match how {
+            IssueAction::Commented(_) => "/commented".to_owned(),
+            IssueAction::Edited(_) => "/edited".to_owned(),
+            IssueAction::ClosedBy(_) => "/closed-by".to_owned(),
+            IssueAction::FixedIn(_, chans) => format!("/fixed-in/{}", chans.join("/")),
+            _ => String::default(),
+        },
+    }
+}
+
+/// Builds the issues feed. Missing archive pages are written on the way.
+pub fn issues_feed(
+    state: &State,
+    options: &FeedOptions,
+    location: &FeedLocation,
+) -> Result<Channel> {
+    issues_builder(state, options, location).build(&state.issue_history)
+}
+
+/// Renders issue history entries like feed items, in order. Entries the
+/// options leave out are `None`.
+pub fn issue_notifications(
+    state: &State,
+    options: &FeedOptions,
+    history: &[(DateTime, String, IssueAction)],
+) -> Result<Vec<Option<Item>>> {
+    issues_builder(state, options, &FeedLocation::default()).notifications(history)
+}
+
+/// Builds the discussions feed. Missing archive pages are written on the way.
+pub fn discussions_feed(
+    state: &State,
+    options: &FeedOptions,
+    location: &FeedLocation,
+) -> Result<Channel> {
+    FeedBuilder {
+        state,
+        items: &state.discussions,
+        options,
+        channel: None,
+        location,
+        tag: &|_, how| {
+            match how {
+                DiscussionAction::New => "[NEW]",
+                DiscussionAction::NewClosed => "[NEW][CLOSED]",
+                DiscussionAction::Answered => "[ANSWERED]",
+                DiscussionAction::Closed => "[CLOSED]",
+                DiscussionAction::Locked => "[LOCKED]",
+            }
+            .to_owned()
+        },
+        id_suffix: &|how| match how {
+            DiscussionAction::Answered => "/answered".to_owned(),
+            DiscussionAction::Locked => "/locked".to_owned(),
+            _ => String::default(),
+        },
+    }
+    .build(&state.discussion_history)
+}
+
+/// Restricts `history` to `Landed` events in channels matching `channel`.
+fn channel_history(
+    history: &[(DateTime, String, PullAction)],
+    channel: &ChannelGlob,
+) -> Vec<(DateTime, String, PullAction)> {
+    history
+        .iter()
+        .filter_map(|(at, id, how)| {
+            let PullAction::Landed(channels) = how else {
+                return None;
+            };
+            let channels = channels
+                .iter()
+                .filter(|c| channel.matches(c))
+                .cloned()
+                .collect::<Vec<_>>();
+            (!channels.is_empty()).then(|| (*at, id.clone(), PullAction::Landed(channels)))
+        })
+        .collect()
+}
+
+fn prs_builder<'a>(
+    state: &'a State,
+    options: &'a FeedOptions,
+    channel: Option<&'a ChannelGlob>,
+    location: &'a FeedLocation,
+) -> FeedBuilder<'a, PullRequest, PullAction> {
+    FeedBuilder {
+        state,
+        items: &state.pull_requests,
+        options,
+        channel,
+        location,
+        tag: &|pr, how| {
+            let (tag, refs) = match how {
+                PullAction::New => ("[NEW]", None),
+                PullAction::NewMerged => ("[NEW][MERGED]", None),
+                PullAction::Closed => ("[CLOSED]", None),
+                PullAction::NewClosed => ("[NEW][CLOSED]", None),
+                PullAction::Merged => ("[MERGED]", None),
+                PullAction::ReadyForReview => ("[READY]", None),
+                PullAction::Approved => ("[APPROVED]", None),
+                PullAction::ChangesRequested => ("[CHANGES-REQUESTED]", None),
+                PullAction::ChecksFailed => ("[CHECKS-FAILED]", None),
+                PullAction::ChecksPassed => ("[CHECKS-PASSED]", None),
+                PullAction::Commented(_) => ("[COMMENTED]", None),
+                PullAction::Edited(_) => ("[EDITED]", None),
+                PullAction::Landed(l) => ("[LANDED]", Some(l.join(" "))),
+                PullAction::Unlanded(l) => ("[UNLANDED]", Some(l.join(" "))),
+                PullAction::Reverted(l) => ("[REVERTED]", Some(l.join(" "))),
+            };
+            let checks = match (how, pr.checks) {
+                (PullAction::ChecksFailed | PullAction::ChecksPassed, _) => "",
+                _ if !pr.is_open => "",
+                (_, Some(checks)) if checks.is_failed() => "[FAILING]",
+                (_, Some(CheckState::Success)) => "[PASSING]",
+                (_, Some(_)) => "[PENDING]",
+                (_, None) => "",
+            };
+            format!(
+                "{}({}){}",
+                tag,
+                refs.as_ref().unwrap_or(&pr.base_ref),
+                checks
+            )
+        },
+        id_suffix: &|how| match how {
+            PullAction::Landed(chans) => format!("/landed/{}", chans.join("/")),
+            PullAction::Unlanded(chans) => format!("/unlanded/{}", chans.join("/")),
+            PullAction::Reverted(chans) => format!("/reverted/{}", chans.join("/")),
+            PullAction::ReadyForReview => "/ready".to_owned(),
+            PullAction::Approved => "/approved".to_owned(),
+            PullAction::ChangesRequested => "/changes-requested".to_owned(),
+            PullAction::ChecksFailed => "/checks-failed".to_owned(),
+            PullAction::ChecksPassed => "/checks-passed".to_owned(),
+            PullAction::Commented(_) => "/commented".to_owned(),
+            PullAction::Edited(_) => "/edited".to_owned(),
+            _ => String::default(),
+        },
+    }
+}
+
+/// Builds the PR feed, or the feed of landings in `channel`. Missing archive
+/// pages are written on the way.
+pub fn prs_feed(
+    state: &State,
+    options: &FeedOptions,
+    channel: Option<&ChannelGlob>,
+    location: &FeedLocation,
+) -> Result<Channel> {
+    let history = match channel {
+        Some(channel) => Cow::Owned(channel_history(&state.pull_history, channel)),
+        None => Cow::Borrowed(&state.pull_history[..]),
+    };
+    prs_builder(state, options, channel, location).build(&history)
+}
+
+/// Renders PR history entries like feed items, in order. Entries the options
+/// leave out are `None`.
+pub fn pull_notifications(
+    state: &State,
+    options: &FeedOptions,
+    history: &[(DateTime, String, PullAction)],
+) -> Result<Vec<Option<Item>>> {
+    prs_builder(state, options, None, &FeedLocation::default()).notifications(history)
+}
+
+pub fn write_feed(to: Option<PathBuf>, channel: &Channel) -> Result<Option<State>> {
+    match to {
+        Some(to) => {
+            let new_file =
+                NamedTempFile::new_in(to.ancestors().nth(1).unwrap_or_else(|| Path::new(".")))?;
+
+            channel.write_to(BufWriter::new(&new_file))?;
+            new_file.persist(to)?;
+        }
+        None => println!("{}", channel.to_string()),
+    }
+    Ok(None)
+}
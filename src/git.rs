@@ -0,0 +1,296 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    process,
+    sync::OnceLock,
+};
+
+use anyhow::{bail, Result};
+use chrono::{TimeZone, Utc};
+use regex::Regex;
+
+use crate::state::DateTime;
+
+/// Matches the message `git revert` leaves in revert commits.
+fn revert_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new("This reverts commit ([0-9a-f]{40})").expect("invalid revert regex")
+    })
+}
+
+/// Bare mirror of a repository used for landing detection.
+pub struct LocalRepo {
+    path: PathBuf,
+}
+
+impl LocalRepo {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        LocalRepo {
+            path: path.as_ref().to_owned(),
+        }
+    }
+
+    pub fn exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    fn git(&self) -> process::Command {
+        let mut cmd = process::Command::new("git");
+        cmd.arg("-C").arg(&self.path);
+        cmd
+    }
+
+    fn output(mut cmd: process::Command, what: &str) -> Result<String> {
+        let out = cmd.output()?;
+        if !out.status.success() {
+            bail!(
+                "{what} failed: {}, {}",
+                out.status,
+                String::from_utf8_lossy(&out.stderr)
+            );
+        }
+        Ok(String::from_utf8(out.stdout)?)
+    }
+
+    /// Fetches `refspecs` from `url` into the mirror, creating it as a partial
+    /// clone if it does not exist yet.
+    pub fn fetch(&self, url: &str, refspecs: &BTreeSet<String>) -> Result<()> {
+        let mut fetch = self.git();
+        fetch.args(["fetch", "--force", "--prune"]);
+        if !self.exists() {
+            let mut init = process::Command::new("git");
+            init.args(["init", "--bare", "--quiet"]).arg(&self.path);
+            Self::output(init, "init")?;
+            // fetching with a filter makes origin a promisor remote, so
+            // missing trees are fetched on demand as with clone --filter.
+            fetch.arg("--filter=tree:0");
+        }
+
+        // reflogs are used to date landings, but bare repos don't keep them
+        // by default.
+        let mut config = self.git();
+        config.args(["config", "core.logAllRefUpdates", "always"]);
+        Self::output(config, "enabling reflogs")?;
+        // set the url directly instead of using `git remote` to not configure
+        // a default refspec. we only ever fetch what we're told to.
+        let mut config = self.git();
+        config.args(["config", "remote.origin.url", url]);
+        Self::output(config, "setting remote url")?;
+
+        if refspecs.is_empty() {
+            return Ok(());
+        }
+        let git_status = fetch.arg("origin").args(refspecs).spawn()?.wait()?;
+        if !git_status.success() {
+            bail!("fetch failed: {git_status}");
+        }
+        Ok(())
+    }
+
+    /// Returns the current commit of every branch and named channel, with
+    /// named channels taking precedence over branches of the same name. Empty
+    /// if the mirror does not exist yet.
+    pub fn channel_tips(&self) -> Result<BTreeMap<String, String>> {
+        if !self.exists() {
+            return Ok(BTreeMap::new());
+        }
+        let mut cmd = self.git();
+        cmd.args([
+            "for-each-ref",
+            "--format=%(objectname) %(refname)",
+            "refs/heads/",
+            "refs/channels/",
+        ]);
+        let refs = Self::output(cmd, "listing branches")?;
+        let refs = refs
+            .lines()
+            .filter_map(|l| l.split_once(' '))
+            .collect::<Vec<_>>();
+        let branches = refs
+            .iter()
+            .filter_map(|(commit, r)| Some((r.strip_prefix("refs/heads/")?, commit)));
+        let channels = refs
+            .iter()
+            .filter_map(|(commit, r)| Some((r.strip_prefix("refs/channels/")?, commit)));
+        Ok(branches
+            .chain(channels)
+            .map(|(name, commit)| (name.to_owned(), (*commit).to_owned()))
+            .collect())
+    }
+
+    /// Points the named channel `name` at `commit`, fetching the commit if
+    /// it is not yet known.
+    pub fn set_channel(&self, name: &str, commit: &str) -> Result<()> {
+        let known = self
+            .git()
+            .args(["cat-file", "-e", &format!("{commit}^{{commit}}")])
+            .status()?
+            .success();
+        if !known {
+            let mut cmd = self.git();
+            cmd.args(["fetch", "--quiet", "origin", commit]);
+            Self::output(cmd, &format!("fetching {commit} for channel {name}"))?;
+        }
+        let mut cmd = self.git();
+        cmd.args(["update-ref", &Self::named_channel_ref(name), commit]);
+        Self::output(cmd, &format!("updating channel {name}"))?;
+        Ok(())
+    }
+
+    pub fn named_channel_ref(name: &str) -> String {
+        format!("refs/channels/{name}")
+    }
+
+    pub fn is_ancestor(&self, ancestor: &str, of: &str) -> Result<bool> {
+        let status = self
+            .git()
+            .args(["merge-base", "--is-ancestor", ancestor, of])
+            .status()?;
+        match status.code() {
+            Some(0) => Ok(true),
+            Some(1) => Ok(false),
+            _ => bail!("ancestry check of {ancestor} in {of} failed: {status}"),
+        }
+    }
+
+    /// Returns all branches matching any of `globs` that contain `commit`.
+    pub fn branches_containing(
+        &self,
+        commit: &str,
+        globs: impl IntoIterator<Item = impl AsRef<std::ffi::OsStr>>,
+    ) -> Result<BTreeSet<String>> {
+        let mut cmd = self.git();
+        cmd.args([
+            "branch",
+            "--format=%(refname:strip=2)",
+            "--contains",
+            commit,
+            "--list",
+        ])
+        .args(globs);
+        Ok(
+            Self::output(cmd, &format!("checking branches containing {commit}"))?
+                .lines()
+                .map(ToOwned::to_owned)
+                .collect(),
+        )
+    }
+
+    /// Returns the commits reverted by `git revert` commits that were added to
+    /// any of `branches` between `old_tips` and `new_tips`, along with the
+    /// branches they were reverted in.
+    pub fn reverts_in(
+        &self,
+        branches: &BTreeSet<String>,
+        old_tips: &BTreeMap<String, String>,
+        new_tips: &BTreeMap<String, String>,
+    ) -> Result<BTreeMap<String, BTreeSet<String>>> {
+        let mut reverted = BTreeMap::<String, BTreeSet<String>>::new();
+        for branch in branches {
+            let (Some(old), Some(new)) = (old_tips.get(branch), new_tips.get(branch)) else {
+                continue;
+            };
+            if old == new {
+                continue;
+            }
+            let mut cmd = self.git();
+            cmd.args([
+                "log",
+                "--format=%B",
+                "--grep=This reverts commit",
+                &format!("{old}..{new}"),
+            ]);
+            let log = Self::output(cmd, &format!("scanning {branch} for reverts"))?;
+            for c in revert_regex().captures_iter(&log) {
+                reverted
+                    .entry(c[1].to_owned())
+                    .or_default()
+                    .insert(branch.clone());
+            }
+        }
+        Ok(reverted)
+    }
+
+    /// Returns the commit reverted by `commit`, if it is a `git revert`
+    /// commit.
+    pub fn reverted_commit(&self, commit: &str) -> Result<Option<String>> {
+        let mut cmd = self.git();
+        cmd.args(["log", "-1", "--format=%B", commit]);
+        let message = Self::output(cmd, &format!("reading message of {commit}"))?;
+        Ok(revert_regex().captures(&message).map(|c| c[1].to_owned()))
+    }
+
+    fn parse_timestamp(ts: &str) -> Option<DateTime> {
+        Utc.timestamp_opt(ts.parse().ok()?, 0).single()
+    }
+
+    /// Estimates when `commit` first appeared in `reference`.
+    ///
+    /// If the reflog of `reference` covers the landing this is the time the ref
+    /// was first updated to contain `commit`, otherwise it is the commit date
+    /// of the first commit along the first-parent chain of `reference` that
+    /// contains `commit`.
+    pub fn landing_time(&self, commit: &str, reference: &str) -> Result<Option<DateTime>> {
+        let mut cmd = self.git();
+        cmd.args([
+            "reflog",
+            "show",
+            "--date=unix",
+            "--format=%H %gd",
+            reference,
+        ]);
+        let reflog = Self::output(cmd, &format!("reading reflog of {reference}"))?;
+        // reflog is newest first. walk back until the branch no longer contains
+        // the commit, the last entry that did is when it landed. if all entries
+        // contain the commit the reflog started after the landing and does not
+        // tell us anything.
+        let mut landed = None;
+        for (tip, at) in reflog.lines().filter_map(|l| l.split_once(' ')) {
+            if !self.is_ancestor(commit, tip)? {
+                if landed.is_some() {
+                    return Ok(landed);
+                }
+                break;
+            }
+            landed = at
+                .rsplit_once("@{")
+                .and_then(|(_, ts)| Self::parse_timestamp(ts.trim_end_matches('}')));
+        }
+
+        let mut cmd = self.git();
+        cmd.args([
+            "log",
+            "--first-parent",
+            "--ancestry-path",
+            "--format=%ct %P",
+            &format!("{commit}..{reference}"),
+        ]);
+        let log = Self::output(cmd, &format!("finding landing of {commit} in {reference}"))?;
+        let oldest = log
+            .lines()
+            .last()
+            .and_then(|l| l.split_once(' '))
+            .filter(|(_, parents)| !parents.starts_with(commit));
+        if let Some((ts, _)) = oldest {
+            return Ok(Self::parse_timestamp(ts));
+        }
+        let mut cmd = self.git();
+        cmd.args(["show", "--no-patch", "--format=%ct", commit]);
+        let ts = Self::output(cmd, &format!("reading commit date of {commit}"))?;
+        Ok(Self::parse_timestamp(ts.trim()))
+    }
+}
+
+/// Builds a refspec that fetches at least all branches matched by `glob`.
+///
+/// Refspecs only support a single `*`, so everything after the first glob
+/// character is replaced by one.
+pub fn glob_refspec(glob: &str) -> String {
+    let branch = if let Some(at) = glob.find(['*', '?', '[', '\\']) {
+        format!("refs/heads/{}*", &glob[..at])
+    } else {
+        format!("refs/heads/{glob}")
+    };
+    format!("{branch}:{branch}")
+}
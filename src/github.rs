@@ -1,10 +1,22 @@
-use std::{collections::BTreeSet, fmt::Debug};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Debug,
+};
 
 use anyhow::{bail, Result};
 use chrono::Duration;
 use graphql_client::{reqwest::post_graphql_blocking as post_graphql, GraphQLQuery};
 
-use crate::state::{DateTime, Issue, PullRequest, HTML, URI};
+use crate::state::{
+    CheckState, Comment, DateTime, Discussion, Issue, ItemRef, PullRequest, Review, ReviewDecision,
+    ReviewState, State, HTML, URI,
+};
+
+use discussions_query::DiscussionsQuerySearchEdgesNode;
+use issue_query::IssueQueryNode;
+use issues_search_query::IssuesSearchQuerySearchEdgesNode;
+use pull_query::PullQueryNode;
+use pulls_search_query::PullsSearchQuerySearchEdgesNode;
 
 const API_URL: &str = "https://api.github.com/graphql";
 
@@ -14,8 +26,12 @@ type GitObjectID = String;
 pub struct Github {
     client: reqwest::blocking::Client,
     owner: String,
-    repo: String,
-    label: String,
+    /// Repo to query, or `None` to query all repos of `owner`.
+    repo: Option<String>,
+    /// Label to query the repo for. Items are searched for if there is none.
+    label: Option<String>,
+    /// Search qualifiers selecting the tracked items.
+    search: String,
 }
 
 trait ChunkedQuery: GraphQLQuery {
@@ -27,6 +43,34 @@ trait ChunkedQuery: GraphQLQuery {
     fn process(&self, d: Self::ResponseData) -> Result<(Vec<Self::Item>, Option<Cursor>)>;
 }
 
+/// Converts a comment, which has distinct types in each query module.
+macro_rules! comment {
+    ($comment:expr, $query:ident) => {{
+        use $query::CommentAuthorAssociation as A;
+        let c = $comment;
+        Comment {
+            author: c.author.map(|a| a.login),
+            by_maintainer: matches!(c.author_association, A::OWNER | A::MEMBER | A::COLLABORATOR),
+            body: c.body_html,
+            created_at: c.created_at,
+            url: c.url,
+        }
+    }};
+}
+
+/// Converts the comments of an issue or PR.
+macro_rules! comments {
+    ($comments:expr, $query:ident) => {
+        $comments
+            .nodes
+            .unwrap_or_default()
+            .into_iter()
+            .flatten()
+            .map(|c| comment!(c, $query))
+            .collect()
+    };
+}
+
 #[derive(Debug, GraphQLQuery)]
 #[graphql(
     schema_path = "vendor/github.com/schema.docs.graphql",
@@ -36,6 +80,95 @@ trait ChunkedQuery: GraphQLQuery {
 )]
 pub struct IssuesQuery;
 
+#[derive(Debug, GraphQLQuery)]
+#[graphql(
+    schema_path = "vendor/github.com/schema.docs.graphql",
+    query_path = "src/issues.graphql",
+    response_derives = "Debug",
+    variables_derives = "Clone,Debug"
+)]
+pub struct IssuesSearchQuery;
+
+#[derive(Debug, GraphQLQuery)]
+#[graphql(
+    schema_path = "vendor/github.com/schema.docs.graphql",
+    query_path = "src/issues.graphql",
+    response_derives = "Debug",
+    variables_derives = "Clone,Debug"
+)]
+pub struct IssueQuery;
+
+/// Implements conversion of the issue fragment, which has distinct types in
+/// each query module.
+macro_rules! impl_issue_from {
+    ($query:ident) => {
+        impl From<$query::IssueFields> for Issue {
+            fn from(n: $query::IssueFields) -> Self {
+                use $query::{
+                    IssueFieldsTimelineItemsNodes as Event,
+                    IssueFieldsTimelineItemsNodesOnClosedEventCloser as Closer,
+                };
+
+                Issue {
+                    id: n.id,
+                    title: n.title,
+                    is_open: !n.closed,
+                    body: n.body_html,
+                    last_update: n.updated_at,
+                    url: n.url,
+                    repo: Some(n.repository.name),
+                    author: n.author.map(|a| a.login),
+                    assignees: n
+                        .assignees
+                        .nodes
+                        .unwrap_or_default()
+                        .into_iter()
+                        .filter_map(|a| Some(a?.login))
+                        .collect(),
+                    milestone: n.milestone.map(|m| m.title),
+                    labels: n
+                        .labels
+                        .and_then(|l| l.nodes)
+                        .unwrap_or_default()
+                        .into_iter()
+                        .filter_map(|l| Some(l?.name))
+                        .collect(),
+                    created_at: Some(n.created_at),
+                    closed_at: n.closed_at,
+                    comment_count: n.comments.total_count.try_into().unwrap_or_default(),
+                    latest_comments: comments!(n.comments, $query),
+                    reported_comments: None,
+                    closed_by: n
+                        .timeline_items
+                        .nodes
+                        .unwrap_or_default()
+                        .into_iter()
+                        .flatten()
+                        .last()
+                        .and_then(|event| {
+                            let Event::ClosedEvent(event) = event else {
+                                return None;
+                            };
+                            let Closer::PullRequest(pr) = event.closer? else {
+                                // closed by a commit pushed directly
+                                return None;
+                            };
+                            Some(ItemRef {
+                                number: pr.number,
+                                title: pr.title,
+                                url: pr.url,
+                            })
+                        }),
+                }
+            }
+        }
+    };
+}
+
+impl_issue_from!(issues_query);
+impl_issue_from!(issues_search_query);
+impl_issue_from!(issue_query);
+
 impl ChunkedQuery for IssuesQuery {
     type Item = Issue;
 
@@ -59,14 +192,7 @@ impl ChunkedQuery for IssuesQuery {
             .unwrap_or_default()
             .into_iter()
             .filter_map(|e| e?.node)
-            .map(|n| Issue {
-                id: n.id,
-                title: n.title,
-                is_open: !n.closed,
-                body: n.body_html,
-                last_update: n.updated_at,
-                url: n.url,
-            })
+            .map(Issue::from)
             .collect();
         let cursor = if issues.page_info.has_next_page {
             issues.page_info.end_cursor
@@ -77,17 +203,182 @@ impl ChunkedQuery for IssuesQuery {
     }
 }
 
+impl ChunkedQuery for IssuesSearchQuery {
+    type Item = Issue;
+
+    fn change_after(&self, v: Self::Variables, after: Option<String>) -> Self::Variables {
+        Self::Variables { after, ..v }
+    }
+    fn set_batch(&self, batch: i64, v: Self::Variables) -> Self::Variables {
+        Self::Variables { batch, ..v }
+    }
+
+    fn process(&self, d: Self::ResponseData) -> Result<(Vec<Self::Item>, Option<Cursor>)> {
+        debug!("rate limits: {:?}", d.rate_limit);
+        let results = d.search;
+        let infos = results
+            .edges
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|e| match e?.node? {
+                IssuesSearchQuerySearchEdgesNode::Issue(n) => Some(Issue::from(n)),
+                _ => None,
+            })
+            .collect();
+        let cursor = if results.page_info.has_next_page {
+            results.page_info.end_cursor
+        } else {
+            None
+        };
+        Ok((infos, cursor))
+    }
+}
+
 #[derive(Debug, GraphQLQuery)]
 #[graphql(
     schema_path = "vendor/github.com/schema.docs.graphql",
     query_path = "src/pulls.graphql",
-    response_derives = "Debug",
+    response_derives = "Debug,Clone",
     variables_derives = "Clone,Debug"
 )]
 pub struct PullsQuery {
     since: Option<DateTime>,
 }
 
+#[derive(Debug, GraphQLQuery)]
+#[graphql(
+    schema_path = "vendor/github.com/schema.docs.graphql",
+    query_path = "src/pulls.graphql",
+    response_derives = "Debug",
+    variables_derives = "Clone,Debug"
+)]
+pub struct PullsSearchQuery;
+
+#[derive(Debug, GraphQLQuery)]
+#[graphql(
+    schema_path = "vendor/github.com/schema.docs.graphql",
+    query_path = "src/pulls.graphql",
+    response_derives = "Debug",
+    variables_derives = "Clone,Debug"
+)]
+pub struct PullQuery;
+
+/// Implements conversion of the PR fragment, which has distinct types in each
+/// query module.
+macro_rules! impl_pull_request_from {
+    ($query:ident) => {
+        impl From<$query::PullRequestFields> for PullRequest {
+            fn from(n: $query::PullRequestFields) -> Self {
+                use $query::{
+                    PullRequestReviewDecision as D, PullRequestReviewState as R, StatusState as S,
+                };
+
+                PullRequest {
+                    id: n.id,
+                    title: n.title,
+                    is_open: !n.closed,
+                    is_merged: n.merged,
+                    body: n.body_html,
+                    last_update: n.updated_at,
+                    url: n.url,
+                    repo: Some(n.repository.name),
+                    base_ref: n.base_ref_name,
+                    merge_commit: n.merge_commit.map(|c| c.oid),
+                    author: n.author.map(|a| a.login),
+                    assignees: n
+                        .assignees
+                        .nodes
+                        .unwrap_or_default()
+                        .into_iter()
+                        .filter_map(|a| Some(a?.login))
+                        .collect(),
+                    milestone: n.milestone.map(|m| m.title),
+                    labels: n
+                        .labels
+                        .and_then(|l| l.nodes)
+                        .unwrap_or_default()
+                        .into_iter()
+                        .filter_map(|l| Some(l?.name))
+                        .collect(),
+                    created_at: Some(n.created_at),
+                    closed_at: n.closed_at,
+                    merged_at: n.merged_at,
+                    head_ref: n.head_ref_name,
+                    is_draft: n.is_draft,
+                    comment_count: n.comments.total_count.try_into().unwrap_or_default(),
+                    latest_comments: comments!(n.comments, $query),
+                    closes: n
+                        .closing_issues_references
+                        .and_then(|r| r.nodes)
+                        .unwrap_or_default()
+                        .into_iter()
+                        .flatten()
+                        .map(|i| ItemRef {
+                            number: i.number,
+                            title: i.title,
+                            url: i.url,
+                        })
+                        .collect(),
+                    review_decision: n.review_decision.and_then(|d| match d {
+                        D::APPROVED => Some(ReviewDecision::Approved),
+                        D::CHANGES_REQUESTED => Some(ReviewDecision::ChangesRequested),
+                        D::REVIEW_REQUIRED => Some(ReviewDecision::ReviewRequired),
+                        D::Other(_) => None,
+                    }),
+                    latest_reviews: n
+                        .latest_reviews
+                        .and_then(|r| r.nodes)
+                        .unwrap_or_default()
+                        .into_iter()
+                        .filter_map(|r| {
+                            let r = r?;
+                            let state = match r.state {
+                                R::APPROVED => ReviewState::Approved,
+                                R::CHANGES_REQUESTED => ReviewState::ChangesRequested,
+                                R::COMMENTED => ReviewState::Commented,
+                                R::DISMISSED => ReviewState::Dismissed,
+                                R::PENDING => ReviewState::Pending,
+                                R::Other(_) => return None,
+                            };
+                            Some(Review {
+                                author: r.author.map(|a| a.login),
+                                state,
+                                submitted_at: r.submitted_at,
+                            })
+                        })
+                        .collect(),
+                    checks: n
+                        .commits
+                        .nodes
+                        .unwrap_or_default()
+                        .into_iter()
+                        .flatten()
+                        .last()
+                        .and_then(|c| c.commit.status_check_rollup)
+                        .and_then(|r| match r.state {
+                            S::ERROR => Some(CheckState::Error),
+                            S::EXPECTED => Some(CheckState::Expected),
+                            S::FAILURE => Some(CheckState::Failure),
+                            S::PENDING => Some(CheckState::Pending),
+                            S::SUCCESS => Some(CheckState::Success),
+                            S::Other(_) => None,
+                        }),
+                    landed_in: BTreeSet::default(),
+                    reverted_in: BTreeSet::default(),
+                    landings_detected: BTreeMap::default(),
+                    held_as_draft: false,
+                    reported_checks: None,
+                    reported_comments: None,
+                }
+            }
+        }
+    };
+}
+
+impl_pull_request_from!(pulls_query);
+impl_pull_request_from!(pulls_search_query);
+impl_pull_request_from!(pull_query);
+
 impl ChunkedQuery for PullsQuery {
     type Item = PullRequest;
 
@@ -111,18 +402,7 @@ impl ChunkedQuery for PullsQuery {
             .unwrap_or_default()
             .into_iter()
             .filter_map(|e| e?.node)
-            .map(|n| PullRequest {
-                id: n.id,
-                title: n.title,
-                is_open: !n.closed,
-                is_merged: n.merged,
-                body: n.body_html,
-                last_update: n.updated_at,
-                url: n.url,
-                base_ref: n.base_ref_name,
-                merge_commit: n.merge_commit.map(|c| c.oid),
-                landed_in: BTreeSet::default(),
-            })
+            .map(PullRequest::from)
             .collect();
         let cursor = match (self.since, infos.last()) {
             (Some(since), Some(last)) if last.last_update < since => None,
@@ -138,13 +418,107 @@ impl ChunkedQuery for PullsQuery {
     }
 }
 
+impl ChunkedQuery for PullsSearchQuery {
+    type Item = PullRequest;
+
+    fn change_after(&self, v: Self::Variables, after: Option<String>) -> Self::Variables {
+        Self::Variables { after, ..v }
+    }
+    fn set_batch(&self, batch: i64, v: Self::Variables) -> Self::Variables {
+        Self::Variables { batch, ..v }
+    }
+
+    fn process(&self, d: Self::ResponseData) -> Result<(Vec<Self::Item>, Option<Cursor>)> {
+        debug!("rate limits: {:?}", d.rate_limit);
+        let results = d.search;
+        let infos = results
+            .edges
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|e| match e?.node? {
+                PullsSearchQuerySearchEdgesNode::PullRequest(n) => Some(PullRequest::from(n)),
+                _ => None,
+            })
+            .collect();
+        let cursor = if results.page_info.has_next_page {
+            results.page_info.end_cursor
+        } else {
+            None
+        };
+        Ok((infos, cursor))
+    }
+}
+
+#[derive(Debug, GraphQLQuery)]
+#[graphql(
+    schema_path = "vendor/github.com/schema.docs.graphql",
+    query_path = "src/discussions.graphql",
+    response_derives = "Debug",
+    variables_derives = "Clone,Debug"
+)]
+pub struct DiscussionsQuery;
+
+impl ChunkedQuery for DiscussionsQuery {
+    type Item = Discussion;
+
+    fn change_after(&self, v: Self::Variables, after: Option<String>) -> Self::Variables {
+        Self::Variables { after, ..v }
+    }
+    fn set_batch(&self, batch: i64, v: Self::Variables) -> Self::Variables {
+        Self::Variables { batch, ..v }
+    }
+
+    fn process(&self, d: Self::ResponseData) -> Result<(Vec<Self::Item>, Option<Cursor>)> {
+        debug!("rate limits: {:?}", d.rate_limit);
+        let results = d.search;
+        let infos = results
+            .edges
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|e| match e?.node? {
+                DiscussionsQuerySearchEdgesNode::Discussion(n) => Some(n),
+                _ => None,
+            })
+            .map(|n| Discussion {
+                id: n.id,
+                title: n.title,
+                is_open: !n.closed,
+                is_locked: n.locked,
+                body: n.body_html,
+                last_update: n.updated_at,
+                url: n.url,
+                repo: Some(n.repository.name),
+                author: n.author.map(|a| a.login),
+                category: n.category.name,
+                labels: n
+                    .labels
+                    .and_then(|l| l.nodes)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|l| Some(l?.name))
+                    .collect(),
+                created_at: n.created_at,
+                closed_at: n.closed_at,
+                answer: n.answer.map(|c| comment!(c, discussions_query)),
+                answered_at: n.answer_chosen_at,
+            })
+            .collect();
+        let cursor = if results.page_info.has_next_page {
+            results.page_info.end_cursor
+        } else {
+            None
+        };
+        Ok((infos, cursor))
+    }
+}
+
 impl Github {
-    pub fn new(api_token: &str, owner: &str, repo: &str, label: &str) -> Result<Self> {
+    pub fn new(api_token: &str, state: &State) -> Result<Self> {
         use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
 
         let headers = match HeaderValue::from_str(&format!("Bearer {api_token}")) {
             Ok(h) => [(AUTHORIZATION, h)].into_iter().collect::<HeaderMap>(),
-            Err(e) => bail!("invalid API token: {}", e),
+            Err(e) => bail!("invalid API token: {e}"),
         };
         let client = reqwest::blocking::Client::builder()
             .user_agent(format!(
@@ -156,9 +530,10 @@ impl Github {
             .build()?;
         Ok(Github {
             client,
-            owner: owner.to_string(),
-            repo: repo.to_string(),
-            label: label.to_string(),
+            owner: state.owner.clone(),
+            repo: state.repo.clone(),
+            label: state.label.clone().filter(|_| state.query.is_none()),
+            search: state.search_filter(),
         })
     }
 
@@ -174,7 +549,7 @@ impl Github {
         loop {
             vars = q.set_batch(batch, vars);
 
-            debug!("running query {:?} with {:?}", q, vars);
+            debug!("running query {q:?} with {vars:?}");
             let started = chrono::Local::now();
             let resp = post_graphql::<Q, _>(&self.client, API_URL, vars.clone())?;
             let ended = chrono::Local::now();
@@ -187,18 +562,18 @@ impl Github {
                     // the batch size again.
                     if batch != max_batch && ended - started < Duration::seconds(8) {
                         batch = (batch + batch / 10 + 1).min(max_batch);
-                        info!("increasing batch size to {}", batch);
+                        info!("increasing batch size to {batch}");
                     }
                     resp
                 }
                 Some(e) if batch > 1 && e.iter().all(|e| e.message.contains("timeout")) => {
-                    warn!("throttling query due to timeout error: {:?}", e);
+                    warn!("throttling query due to timeout error: {e:?}");
                     // anything larger than 1 seems to be unreliable here
                     batch = 1;
-                    info!("new batch size: {}", batch);
+                    info!("new batch size: {batch}");
                     continue;
                 }
-                Some(e) => bail!("query failed: {:?}", e),
+                Some(e) => bail!("query failed: {e:?}"),
             };
 
             match resp.data {
@@ -217,28 +592,188 @@ impl Github {
         Ok(result)
     }
 
-    pub fn query_issues(&self, since: Option<DateTime>) -> Result<Vec<Issue>> {
+    fn query_one<Q: GraphQLQuery>(&self, vars: Q::Variables) -> Result<Q::ResponseData> {
+        let resp = post_graphql::<Q, _>(&self.client, API_URL, vars)?;
+        match (resp.data, resp.errors) {
+            (_, Some(e)) => bail!("query failed: {e:?}"),
+            (Some(d), None) => Ok(d),
+            (None, None) => bail!("query returned no data"),
+        }
+    }
+
+    /// Queries a single issue by its node id, with up to `comments` of its
+    /// latest comments.
+    pub fn query_issue(&self, id: &str, comments: i64) -> Result<Option<Issue>> {
+        let d = self.query_one::<IssueQuery>(issue_query::Variables {
+            id: id.to_owned(),
+            comments,
+        })?;
+        debug!("rate limits: {:?}", d.rate_limit);
+        match d.node {
+            Some(IssueQueryNode::Issue(n)) => Ok(Some(n.into())),
+            _ => Ok(None),
+        }
+    }
+
+    /// Queries a single PR by its node id, with up to `comments` of its latest
+    /// comments.
+    pub fn query_pull(&self, id: &str, comments: i64) -> Result<Option<PullRequest>> {
+        let d = self.query_one::<PullQuery>(pull_query::Variables {
+            id: id.to_owned(),
+            comments,
+        })?;
+        debug!("rate limits: {:?}", d.rate_limit);
+        match d.node {
+            Some(PullQueryNode::PullRequest(n)) => Ok(Some(n.into())),
+            _ => Ok(None),
+        }
+    }
+
+    /// Search terms narrowing the tracked search down to the item at `url`.
+    /// Searches can't select numbers directly, but match them as text. The
+    /// search index lags behind webhooks, so these must not depend on
+    /// anything that just changed, like the update time.
+    fn item_terms(&self, url: &str, repo: Option<&str>) -> String {
+        let number = url.rsplit('/').next().unwrap_or_default();
+        match repo {
+            Some(repo) => format!("repo:{}/{repo} {number}", self.owner),
+            None => number.to_owned(),
+        }
+    }
+
+    /// Whether `issue` matches the tracked search.
+    pub fn search_matches_issue(&self, issue: &Issue) -> Result<bool> {
+        let terms = self.item_terms(&issue.url, issue.repo.as_deref());
+        let found = self.query_raw(
+            &IssuesSearchQuery,
+            issues_search_query::Variables {
+                query: self.search_query(&format!("is:issue {terms}"), None),
+                after: None,
+                comments: 0,
+                batch: 100,
+            },
+        )?;
+        Ok(found.iter().any(|i| i.id == issue.id))
+    }
+
+    /// Whether `pr` matches the tracked search.
+    pub fn search_matches_pull(&self, pr: &PullRequest) -> Result<bool> {
+        let terms = self.item_terms(&pr.url, pr.repo.as_deref());
+        let found = self.query_raw(
+            &PullsSearchQuery,
+            pulls_search_query::Variables {
+                query: self.search_query(&format!("is:pr {terms}"), None),
+                after: None,
+                comments: 0,
+                batch: 100,
+            },
+        )?;
+        Ok(found.iter().any(|p| p.id == pr.id))
+    }
+
+    /// Search query for tracked items matching `qualifiers` updated since
+    /// `since`, oldest first.
+    fn search_query(&self, qualifiers: &str, since: Option<DateTime>) -> String {
+        let since = since.map(|s| format!("updated:>={}", s.format("%Y-%m-%dT%H:%M:%SZ")));
+        [&self.search, qualifiers, "sort:updated-asc"]
+            .into_iter()
+            .chain(since.as_deref())
+            .filter(|q| !q.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Queries issues updated since `since`, with up to `comments` of their
+    /// latest comments.
+    pub fn query_issues(&self, since: Option<DateTime>, comments: i64) -> Result<Vec<Issue>> {
+        let (Some(repo), Some(label)) = (&self.repo, &self.label) else {
+            return self.query_raw(
+                &IssuesSearchQuery,
+                issues_search_query::Variables {
+                    query: self.search_query("is:issue", since),
+                    after: None,
+                    comments,
+                    batch: 100,
+                },
+            );
+        };
         self.query_raw(
             &IssuesQuery,
             issues_query::Variables {
                 owner: self.owner.clone(),
-                name: self.repo.clone(),
-                label: self.label.clone(),
+                name: repo.clone(),
+                label: label.clone(),
                 after: None,
                 since,
+                comments,
                 batch: 100,
             },
         )
     }
 
-    pub fn query_pulls(&self, since: Option<DateTime>) -> Result<Vec<PullRequest>> {
+    pub fn query_pulls(&self, since: Option<DateTime>, comments: i64) -> Result<Vec<PullRequest>> {
+        let (Some(repo), Some(label)) = (&self.repo, &self.label) else {
+            return self.query_raw(
+                &PullsSearchQuery,
+                pulls_search_query::Variables {
+                    query: self.search_query("is:pr", since),
+                    after: None,
+                    comments,
+                    batch: 100,
+                },
+            );
+        };
         self.query_raw(
             &PullsQuery { since },
             pulls_query::Variables {
                 owner: self.owner.clone(),
-                name: self.repo.clone(),
-                label: self.label.clone(),
+                name: repo.clone(),
+                label: label.clone(),
+                after: None,
+                states: None,
+                comments,
+                batch: 100,
+            },
+        )
+    }
+
+    /// Queries discussions updated since `since`. Discussions can't be
+    /// filtered by label directly, so this uses the search API. Searches
+    /// return at most 1000 results, oldest first so later syncs pick up the
+    /// rest.
+    pub fn query_discussions(&self, since: Option<DateTime>) -> Result<Vec<Discussion>> {
+        self.query_raw(
+            &DiscussionsQuery,
+            discussions_query::Variables {
+                query: self.search_query("", since),
+                after: None,
+                batch: 100,
+            },
+        )
+    }
+
+    /// Queries all open PRs regardless of when they were last updated.
+    pub fn query_open_pulls(&self, comments: i64) -> Result<Vec<PullRequest>> {
+        let (Some(repo), Some(label)) = (&self.repo, &self.label) else {
+            return self.query_raw(
+                &PullsSearchQuery,
+                pulls_search_query::Variables {
+                    query: self.search_query("is:pr is:open", None),
+                    after: None,
+                    comments,
+                    batch: 100,
+                },
+            );
+        };
+        self.query_raw(
+            &PullsQuery { since: None },
+            pulls_query::Variables {
+                owner: self.owner.clone(),
+                name: repo.clone(),
+                label: label.clone(),
                 after: None,
+                states: Some(vec![pulls_query::PullRequestState::OPEN]),
+                comments,
                 batch: 100,
             },
         )
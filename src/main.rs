@@ -1,77 +1,47 @@
 #![warn(clippy::pedantic, clippy::cargo)]
-#![allow(clippy::cargo_common_metadata)]
+#![allow(clippy::cargo_common_metadata, clippy::module_name_repetitions)]
 
 #[macro_use]
 extern crate anyhow;
 #[macro_use]
 extern crate log;
 
+mod channels;
+mod feed;
 mod full_matchable_regex;
+mod git;
 mod github;
+mod notify;
 mod state;
+mod webhook;
 
 use std::{
-    borrow::ToOwned,
     collections::{btree_map::Entry, BTreeMap, BTreeSet},
     env,
-    fs::File,
+    fs::{self, File},
     io::BufWriter,
     path::{Path, PathBuf},
-    process,
     str::FromStr,
 };
 
 use anyhow::{Context, Result};
-use chrono::{Duration, Utc};
+use channels::{ChannelGlob, ChannelPatterns, NamedChannel};
+use chrono::Utc;
 use clap::{Args, Parser, Subcommand};
-use full_matchable_regex::FullMatchableRegex;
+use feed::{
+    discussions_feed, issues_feed, prs_feed, write_feed, ActionFilter, Archive, FeedLocation,
+    FeedOptions, ItemFilter, Templates,
+};
+use fs2::FileExt;
+use git::LocalRepo;
 use github::Github;
-use regex::Regex;
-use rss::{Channel, ChannelBuilder, Guid, Item, ItemBuilder};
 use serde_json::to_writer;
-use state::{DateTime, IssueAction, PullAction, State, STATE_VERSION};
+use state::{
+    Action, CheckState, Comment, DateTime, DiscussionAction, Issue, IssueAction, PullAction,
+    PullRequest, ReviewDecision, State, STATE_VERSION,
+};
 use tempfile::NamedTempFile;
 
-#[derive(Debug)]
-struct ChannelPatterns {
-    patterns: Vec<(Regex, Vec<String>)>,
-}
-
-impl ChannelPatterns {
-    fn find_channels(&self, target: &str) -> BTreeSet<String> {
-        self.patterns
-            .iter()
-            .filter(|(target_regex, _)| target_regex.is_full_match(target))
-            .flat_map(|(target_regex, channel_globs)| {
-                channel_globs
-                    .iter()
-                    .map(|channel_glob| target_regex.replace(target, channel_glob).to_string())
-            })
-            .collect()
-    }
-}
-
-impl FromStr for ChannelPatterns {
-    type Err = anyhow::Error;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let patterns = s
-            .split(',')
-            .map(|s| match s.trim().split_once(':') {
-                Some((target_regex, channel_globs)) => Ok((
-                    Regex::new(target_regex)?,
-                    channel_globs
-                        .split_whitespace()
-                        .map(ToOwned::to_owned)
-                        .collect::<Vec<_>>(),
-                )),
-                None => bail!("invalid channel pattern `{s}`"),
-            })
-            .collect::<Result<_>>()?;
-        Ok(ChannelPatterns { patterns })
-    }
-}
-
 #[derive(Parser)]
 #[clap(version)]
 /// Poll github issues and PRs by label and generate RSS feeds.
@@ -85,7 +55,9 @@ struct App {
 enum Command {
     /// Initialize a tracker state.
     ///
-    /// Each tracker state applies to only one repository and only one label.
+    /// Each tracker state applies to only one repository and only one label
+    /// or search query. See `init-owner` to track items in all repositories
+    /// of an owner.
     Init {
         /// Path of the newly created state.
         state_file: PathBuf,
@@ -93,23 +65,108 @@ enum Command {
         owner: String,
         /// Name of the repository.
         repo: String,
-        /// Name of the label to track.
-        label: String,
+        #[clap(flatten)]
+        tracked: TrackedArgs,
+        /// Git URL to fetch channel branches from for landing detection.
+        /// Defaults to the repository on github.
+        #[clap(long)]
+        remote: Option<String>,
+    },
+    /// Initialize a tracker state for all repositories of an owner.
+    ///
+    /// Items are found with the search API. Channel patterns match PR targets
+    /// as `repo/branch`, and landing detection keeps one mirror per repository
+    /// in the local repo directory.
+    InitOwner {
+        /// Path of the newly created state.
+        state_file: PathBuf,
+        /// User or organization owning the repositories to query.
+        owner: String,
+        #[clap(flatten)]
+        tracked: TrackedArgs,
     },
     /// Sync issues on a state.
     SyncIssues(SyncIssuesArgs),
     /// Sync pull requests on a state.
     SyncPrs(SyncPrsArgs),
+    /// Sync discussions on a state.
+    SyncDiscussions {
+        /// State to sync.
+        state_file: PathBuf,
+    },
+    /// Show the channels each PR target in a state maps to.
+    CheckPatterns(CheckPatternsArgs),
     /// Emit an RSS feed for issue changes.
     EmitIssues(EmitArgs),
     /// Emit an RSS feed for PR changes.
-    EmitPrs(EmitArgs),
+    EmitPrs(EmitPrsArgs),
+    /// Emit an RSS feed for discussion changes.
+    EmitDiscussions(EmitArgs),
+    /// Apply github webhook events to states as they are delivered.
+    ///
+    /// Handles `issues`, `pull_request`, `label` and `push` events of the
+    /// tracked repositories. Deliveries must be signed with the secret in
+    /// `GITHUB_WEBHOOK_SECRET`, and are answered before their events are
+    /// applied. Events don't move the sync cursors, so
+    /// `sync-issues` and `sync-prs` should still run periodically to pick up
+    /// missed deliveries.
+    Webhook(WebhookArgs),
+    /// Deliver history appended since the last run to notification sinks.
+    ///
+    /// Entries are delivered in the order they were recorded. Sinks that
+    /// fail are retried from the failed entry on the next run.
+    Notify(NotifyArgs),
+}
+
+#[derive(Args)]
+struct TrackedArgs {
+    /// Name of the label to track.
+    #[clap(required_unless_present = "query")]
+    label: Option<String>,
+
+    /// Track items matching this search query instead of a label, eg.
+    /// `is:pr author:app/dependabot`. Items are searched for in the tracked
+    /// repositories, so the query must not contain `repo:`, `user:`, `org:`
+    /// or `sort:` qualifiers.
+    #[clap(long, conflicts_with = "label")]
+    query: Option<SearchQuery>,
+}
+
+/// A search query that leaves scope and order to the tracker.
+struct SearchQuery(String);
+
+impl FromStr for SearchQuery {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // quoted terms are searched for as text, not taken as qualifiers.
+        let mut quoted = false;
+        let terms = s.split(|c: char| {
+            quoted ^= c == '"';
+            !quoted && c.is_whitespace()
+        });
+        for term in terms {
+            let Some((qualifier, _)) = term.trim_start_matches('-').split_once(':') else {
+                continue;
+            };
+            if ["sort", "repo", "user", "org"].contains(&qualifier.to_lowercase().as_str()) {
+                bail!("queries must not contain `{qualifier}:`, the tracker sets scope and order");
+            }
+        }
+        Ok(SearchQuery(s.to_owned()))
+    }
 }
 
 #[derive(Args)]
 struct SyncIssuesArgs {
     /// State to sync.
     state_file: PathBuf,
+
+    #[clap(flatten)]
+    comments: CommentArgs,
+
+    #[clap(flatten)]
+    edits: EditArgs,
 }
 
 #[derive(Args)]
@@ -117,13 +174,248 @@ struct SyncPrsArgs {
     /// State to sync.
     state_file: PathBuf,
 
-    /// Path to git repo used for landing detection.
+    /// Path to git repo used for landing detection. Required if any
+    /// patterns are given.
     #[clap(short = 'l', long)]
-    local_repo: PathBuf,
+    local_repo: Option<PathBuf>,
+
+    #[clap(flatten)]
+    prs: PrArgs,
+}
+
+/// How PRs are tracked, shared by `sync-prs` and `webhook`.
+#[derive(Args)]
+struct PrArgs {
+    #[clap(flatten)]
+    patterns: PatternArgs,
+
+    /// Report landed PRs whose merge commit was reverted in a channel.
+    #[clap(long)]
+    detect_reverts: bool,
+
+    /// Don't report new PRs while they are drafts. They are reported as new
+    /// once they are ready for review, merged or closed.
+    #[clap(long)]
+    hold_drafts: bool,
+
+    /// Fetch all open PRs on every sync to notice changed CI check results.
+    /// Check results don't count as PR updates, so otherwise they are only
+    /// noticed when the PR changes in some other way.
+    #[clap(long)]
+    poll_checks: bool,
+
+    #[clap(flatten)]
+    comments: CommentArgs,
+
+    #[clap(flatten)]
+    edits: EditArgs,
+
+    /// Channel resolved through a URL instead of a branch, as `NAME=URL`.
+    /// The URL must return the commit the channel is currently at. Patterns
+    /// naming a channel exactly check landing in it instead of in a branch.
+    #[clap(short = 'c', long = "channel")]
+    named_channels: Vec<NamedChannel>,
+}
+
+impl PrArgs {
+    fn tracking(&self) -> PullTracking {
+        PullTracking {
+            hold_drafts: self.hold_drafts,
+            poll_checks: self.poll_checks,
+            comments: self.comments,
+            edits: self.edits,
+        }
+    }
+
+    fn landing<'a>(
+        &'a self,
+        local_repo: Option<&'a Path>,
+        patterns: &'a ChannelPatterns,
+    ) -> Result<Option<LandingDetection<'a>>> {
+        match local_repo {
+            _ if patterns.is_empty() => Ok(None),
+            Some(local_repo) => Ok(Some(LandingDetection {
+                local_repo,
+                patterns,
+                named_channels: &self.named_channels,
+                detect_reverts: self.detect_reverts,
+            })),
+            None => bail!("landing detection requires a local repo"),
+        }
+    }
+}
+
+#[derive(Args)]
+struct WebhookArgs {
+    /// States to apply events to. Each event is applied to the states
+    /// tracking its repository.
+    #[clap(required = true)]
+    state_files: Vec<PathBuf>,
+
+    /// Address to listen for deliveries on.
+    #[clap(long, default_value = "127.0.0.1:8080")]
+    listen: String,
+
+    /// Issues are tracked with the same comment and edit settings as PRs.
+    /// Landings are detected in a mirror next to each state, named like the
+    /// state with `.git` appended.
+    #[clap(flatten)]
+    prs: PrArgs,
+}
+
+#[derive(Args)]
+struct NotifyArgs {
+    /// State to read new history from.
+    state_file: PathBuf,
+
+    /// JSON file mapping sink names to sinks, eg.
+    /// `{"ops": {"type": "slack", "url": "https://hooks.slack.com/…"}}`.
+    /// Sink types are `json`, `slack`, `matrix` (hookshot webhooks) and
+    /// `email` (with `server`, `from`, `to` and optionally `port` and
+    /// `username`, the password is read from `SMTP_PASSWORD`). Renaming a
+    /// sink makes it start over with new history.
+    #[clap(short, long)]
+    sinks: PathBuf,
+}
+
+#[derive(Args, Clone, Copy)]
+struct CommentArgs {
+    /// Report new comments, summarized once per sync.
+    #[clap(long)]
+    comments: bool,
+
+    /// Only report comments once at least this many are new.
+    #[clap(long, value_name = "COUNT", default_value_t = 1)]
+    min_comments: u64,
+
+    /// Only report comments if one of the new ones is by a maintainer, ie.
+    /// an owner, member or collaborator of the repo.
+    #[clap(long)]
+    maintainer_comments: bool,
+}
+
+impl CommentArgs {
+    /// Number of latest comments to fetch with each item.
+    fn window(self) -> i64 {
+        if self.comments {
+            10
+        } else {
+            0
+        }
+    }
+
+    /// Returns the number of new comments to report for an item that has
+    /// `count` comments, and updates `reported` to match.
+    fn new_comments(
+        self,
+        reported: &mut Option<u64>,
+        count: u64,
+        latest: &[Comment],
+    ) -> Option<u64> {
+        let Some(last) = *reported else {
+            *reported = Some(count);
+            return None;
+        };
+        let new = count.saturating_sub(last);
+        // comments may have been deleted, and must not be held back when
+        // comment tracking is off.
+        if !self.comments || count < last {
+            *reported = Some(count);
+            return None;
+        }
+        if new == 0 || new < self.min_comments {
+            return None;
+        }
+        let by_maintainer = latest
+            .iter()
+            .rev()
+            .take(new.try_into().unwrap_or(usize::MAX))
+            .any(|c| c.by_maintainer);
+        if self.maintainer_comments && !by_maintainer {
+            return None;
+        }
+        *reported = Some(count);
+        Some(new)
+    }
+}
+
+#[derive(Args, Clone, Copy)]
+struct EditArgs {
+    /// Report title changes, along with the previous title.
+    #[clap(long)]
+    edits: bool,
+
+    /// Also report body changes of at least this many bytes.
+    #[clap(long, value_name = "BYTES", requires = "edits")]
+    body_edit_threshold: Option<usize>,
+}
+
+impl EditArgs {
+    /// Returns the `edited` event for a change of title and body from `old`
+    /// to `new`, if it is to be reported.
+    fn edit<A>(
+        self,
+        old: (&str, &str),
+        new: (&str, &str),
+        edited: impl FnOnce(Option<String>) -> A,
+    ) -> Option<A> {
+        let ((old_title, old_body), (new_title, new_body)) = (old, new);
+        if !self.edits {
+            None
+        } else if old_title != new_title {
+            Some(edited(Some(old_title.to_owned())))
+        } else {
+            let threshold = self.body_edit_threshold?;
+            (changed_bytes(old_body, new_body) >= threshold).then(|| edited(None))
+        }
+    }
+}
+
+/// Size of the part of `old` that was replaced to get `new`, or of its
+/// replacement if that is larger.
+fn changed_bytes(old: &str, new: &str) -> usize {
+    let (old, new) = (old.as_bytes(), new.as_bytes());
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let (old, new) = (&old[prefix..], &new[prefix..]);
+    let suffix = old
+        .iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    (old.len() - suffix).max(new.len() - suffix)
+}
 
-    /// PR landing patterns.
+#[derive(Args)]
+struct PatternArgs {
+    /// PR landing patterns, as `TARGET:CHANNEL...,...`. Landing detection is
+    /// skipped if none are given.
     #[clap(short = 'p', long)]
-    patterns: ChannelPatterns,
+    patterns: Option<ChannelPatterns>,
+
+    /// JSON file mapping target regexes to lists of channels, as an
+    /// alternative to --patterns.
+    #[clap(short = 'P', long, conflicts_with = "patterns")]
+    patterns_file: Option<PathBuf>,
+}
+
+impl PatternArgs {
+    fn load(&mut self) -> Result<ChannelPatterns> {
+        match (self.patterns.take(), &self.patterns_file) {
+            (Some(patterns), _) => Ok(patterns),
+            (None, Some(file)) => ChannelPatterns::from_file(file),
+            (None, None) => Ok(ChannelPatterns::default()),
+        }
+    }
+}
+
+#[derive(Args)]
+struct CheckPatternsArgs {
+    /// State to read targets from.
+    state_file: PathBuf,
+
+    #[clap(flatten)]
+    patterns: PatternArgs,
 }
 
 #[derive(Args)]
@@ -138,6 +430,182 @@ struct EmitArgs {
     #[clap(short, long)]
     /// Target file for the generated feed. Defaults to stdout.
     out: Option<PathBuf>,
+
+    #[clap(long, use_value_delimiter = true)]
+    /// Only include these actions, eg. `merged,landed`.
+    include: Vec<String>,
+
+    #[clap(long, use_value_delimiter = true)]
+    /// Leave out these actions, eg. `newclosed`.
+    exclude: Vec<String>,
+
+    #[clap(long)]
+    /// Only include items by this author. May be given multiple times.
+    author: Vec<String>,
+
+    #[clap(long)]
+    /// Only include items in this milestone. May be given multiple times.
+    milestone: Vec<String>,
+
+    #[clap(long)]
+    /// Only include items with this label. May be given multiple times.
+    label: Vec<String>,
+
+    #[clap(long, value_name = "TEMPLATE")]
+    /// Template for the feed title. Templates use minijinja syntax, a
+    /// leading `@` reads the template from a file instead.
+    title_template: Option<String>,
+
+    #[clap(long, value_name = "TEMPLATE")]
+    /// Template for item titles, eg. `{{ tag }} {{ item.title }}`.
+    item_title_template: Option<String>,
+
+    #[clap(long, value_name = "TEMPLATE")]
+    /// Template for item contents, eg. `{{ item.body }}`.
+    item_content_template: Option<String>,
+
+    #[clap(long)]
+    /// Strip item contents down to an allowlist of tags and attributes and
+    /// make relative links absolute.
+    sanitize: bool,
+
+    #[clap(long, value_name = "BYTES")]
+    /// Cut item contents longer than this short and link to the full item.
+    max_body_length: Option<usize>,
+
+    #[clap(long, value_name = "COUNT")]
+    /// Only include this many entries. With an archive, entries not
+    /// archived yet are counted first and must fit, so this may not be less
+    /// than the archive page size.
+    max_items: Option<usize>,
+
+    #[clap(long, value_name = "DIR", requires = "archive-url")]
+    /// Keep an RFC 5005 archive of older history in this directory. Pages
+    /// are written once they are full and linked from the feed, entries not
+    /// archived yet are always included in the feed.
+    archive_dir: Option<PathBuf>,
+
+    #[clap(long, value_name = "URL", requires = "archive-dir")]
+    /// URL the archive directory is published at.
+    archive_url: Option<String>,
+
+    #[clap(long, value_name = "COUNT", default_value_t = 50)]
+    /// Number of history entries per archive page. Entries left out by
+    /// filters are left out of their page, so pages can hold fewer items.
+    /// Must not change once pages were written.
+    archive_page_size: usize,
+
+    #[clap(long, value_name = "URL")]
+    /// URL the feed is published at. For per-channel feeds this is the URL
+    /// of the feed directory.
+    self_url: Option<String>,
+
+    #[clap(long, value_name = "MINUTES")]
+    /// How long readers may cache the feed.
+    ttl: Option<u32>,
+}
+
+impl EmitArgs {
+    fn feed_options<A: Action>(&self, default_title: &str) -> Result<FeedOptions> {
+        Ok(FeedOptions {
+            age_hours: self.age_hours,
+            actions: ActionFilter::new::<A>(&self.include, &self.exclude)?,
+            templates: Templates::new(
+                self.title_template.as_deref().unwrap_or(default_title),
+                self.item_title_template
+                    .as_deref()
+                    .unwrap_or(feed::DEFAULT_ITEM_TITLE),
+                self.item_content_template
+                    .as_deref()
+                    .unwrap_or(feed::DEFAULT_ITEM_CONTENT),
+            )?,
+            sanitize: self.sanitize,
+            max_body_length: self.max_body_length,
+            max_items: self.max_items,
+            filter: ItemFilter {
+                authors: self.author.clone(),
+                milestones: self.milestone.clone(),
+                labels: self.label.clone(),
+            },
+            ttl: self.ttl,
+        })
+    }
+
+    fn location(&self) -> Result<FeedLocation> {
+        let archive = match (&self.archive_dir, &self.archive_url) {
+            (Some(dir), Some(url)) => {
+                if self.archive_page_size == 0 {
+                    bail!("archive pages must hold at least one entry");
+                }
+                if self
+                    .max_items
+                    .is_some_and(|max| max < self.archive_page_size)
+                {
+                    bail!("--max-items must be at least --archive-page-size");
+                }
+                Some(Archive {
+                    dir: dir.clone(),
+                    url: url.clone(),
+                    page_size: self.archive_page_size,
+                })
+            }
+            _ => None,
+        };
+        Ok(FeedLocation {
+            url: self.self_url.clone(),
+            archive,
+        })
+    }
+}
+
+#[derive(Args)]
+struct EmitPrsArgs {
+    #[clap(flatten)]
+    common: EmitArgs,
+
+    #[clap(short, long, conflicts_with = "per-channel")]
+    /// Only include landing events for channels matching this glob. Unlanded
+    /// and reverted events are left out.
+    channel: Option<ChannelGlob>,
+
+    #[clap(long, value_name = "DIR", conflicts_with = "out")]
+    /// Write one feed of landing events per channel into this directory,
+    /// named after the channel.
+    per_channel: Option<PathBuf>,
+}
+
+fn create_state(state_file: PathBuf, state: &State) -> Result<()> {
+    let file = File::options()
+        .create_new(true)
+        .write(true)
+        .open(state_file)?;
+    to_writer(file, state)?;
+    Ok(())
+}
+
+/// Locks `state_file` against concurrent updates until the returned lock
+/// is dropped. Syncs, webhook deliveries and notifications may race for the
+/// same state.
+fn lock_state(state_file: impl AsRef<Path>) -> Result<File> {
+    let lock_file = state_file.as_ref().with_extension("lock");
+    let lock = File::create(&lock_file)
+        .with_context(|| format!("failed to create {}", lock_file.display()))?;
+    lock.lock_exclusive()?;
+    Ok(lock)
+}
+
+fn write_state(state_file: impl AsRef<Path>, state: &State) -> Result<()> {
+    let new_state_file = NamedTempFile::new_in(
+        state_file
+            .as_ref()
+            .ancestors()
+            .nth(1)
+            .unwrap_or_else(|| Path::new(".")),
+    )?;
+
+    to_writer(BufWriter::new(&new_state_file), state)?;
+    new_state_file.persist(state_file)?;
+    Ok(())
 }
 
 fn with_state_and_github<F>(state_file: impl AsRef<Path>, f: F) -> Result<()>
@@ -147,54 +615,167 @@ where
     let github_api_token =
         env::var("GITHUB_API_TOKEN").context("failed to load GITHUB_API_TOKEN")?;
 
+    let _lock = lock_state(&state_file)?;
+
     let old_state = State::from_file(&state_file)?;
 
-    let client = github::Github::new(
-        &github_api_token,
-        &old_state.owner,
-        &old_state.repo,
-        &old_state.label,
-    )?;
+    let client = github::Github::new(&github_api_token, &old_state)?;
 
     let new_state = f(old_state, &client)?;
 
     if let Some(state) = new_state {
-        let new_state_file = NamedTempFile::new_in(
-            state_file
-                .as_ref()
-                .ancestors()
-                .nth(1)
-                .unwrap_or_else(|| Path::new(".")),
-        )?;
-
-        to_writer(BufWriter::new(&new_state_file), &state)?;
-        new_state_file.persist(state_file)?;
+        write_state(state_file, &state)?;
     }
 
     Ok(())
 }
 
-fn sync_issues(mut state: State, github: &github::Github) -> Result<Option<State>> {
-    let issues = github.query_issues(state.issues_updated)?;
+/// Delivers new history to all configured sinks. Sinks seen for the
+/// first time only get history appended after this.
+fn notify(cmd: &NotifyArgs) -> Result<()> {
+    let sinks = notify::load_sinks(&cmd.sinks)?;
+    let options = FeedOptions {
+        age_hours: 0,
+        actions: ActionFilter::new::<IssueAction>(&[], &[])?,
+        templates: Templates::new(
+            feed::DEFAULT_ISSUES_TITLE,
+            feed::DEFAULT_ITEM_TITLE,
+            feed::DEFAULT_ITEM_CONTENT,
+        )?,
+        sanitize: true,
+        max_body_length: None,
+        max_items: None,
+        filter: ItemFilter::default(),
+        ttl: None,
+    };
+    let http = reqwest::blocking::Client::new();
+
+    let _lock = lock_state(&cmd.state_file)?;
+    let mut state = State::from_file(&cmd.state_file)?;
+
+    let failed = notify::deliver_all(&http, &sinks, &mut state, &options, |state| {
+        write_state(&cmd.state_file, state)
+    })?;
+    if failed > 0 {
+        bail!("{failed} of {} sinks failed", sinks.len());
+    }
+    Ok(())
+}
+
+fn sync_issues(
+    mut state: State,
+    github: &github::Github,
+    tracking: &IssueTracking,
+) -> Result<Option<State>> {
+    let issues = github.query_issues(state.issues_updated, tracking.comments.window())?;
 
     let mut new_history = vec![];
 
     for updated in issues {
-        let issue_state = |is_new| match (updated.is_open, is_new) {
-            (true, _) => IssueAction::New,
-            (false, true) => IssueAction::NewClosed,
-            (false, false) => IssueAction::Closed,
+        update_issue(&mut state, updated, tracking, &mut new_history);
+    }
+
+    new_history.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+    if let Some(&(at, _, _)) = new_history.last() {
+        state.issues_updated = Some(at);
+    }
+    state.issue_history.append(&mut new_history);
+
+    Ok(Some(state))
+}
+
+fn update_issue(
+    state: &mut State,
+    mut updated: Issue,
+    tracking: &IssueTracking,
+    new_history: &mut Vec<(DateTime, String, IssueAction)>,
+) {
+    // closing PRs are reported as an extra entry so filters and readers
+    // following `Closed` keep seeing every close.
+    let issue_state = |is_new| {
+        let (how, closed_by) = match (updated.is_open, is_new) {
+            (true, _) => (IssueAction::New, None),
+            (false, true) => (IssueAction::NewClosed, updated.closed_by.as_ref()),
+            (false, false) => (IssueAction::Closed, updated.closed_by.as_ref()),
         };
-        match state.issues.entry(updated.id.clone()) {
+        let closed_by = closed_by.map(|pr| IssueAction::ClosedBy(pr.url.clone()));
+        [how]
+            .into_iter()
+            .chain(closed_by)
+            .map(|how| (updated.last_update, updated.id.clone(), how))
+            .collect::<Vec<_>>()
+    };
+    match state.issues.entry(updated.id.clone()) {
+        Entry::Occupied(mut e) => {
+            let stored = e.get_mut();
+            if stored.is_open != updated.is_open {
+                new_history.extend(issue_state(false));
+            }
+            let edit = tracking.edits.edit(
+                (&stored.title, &stored.body),
+                (&updated.title, &updated.body),
+                IssueAction::Edited,
+            );
+            if let Some(how) = edit {
+                new_history.push((updated.last_update, updated.id.clone(), how));
+            }
+            updated.reported_comments = stored.reported_comments;
+            let new = tracking.comments.new_comments(
+                &mut updated.reported_comments,
+                updated.comment_count,
+                &updated.latest_comments,
+            );
+            if let Some(new) = new {
+                new_history.push((
+                    updated.last_update,
+                    updated.id.clone(),
+                    IssueAction::Commented(new),
+                ));
+            }
+            *stored = updated;
+        }
+        Entry::Vacant(e) => {
+            new_history.extend(issue_state(true));
+            updated.reported_comments = Some(updated.comment_count);
+            e.insert(updated);
+        }
+    }
+}
+
+fn sync_discussions(mut state: State, github: &github::Github) -> Result<Option<State>> {
+    let discussions = github.query_discussions(state.discussions_updated)?;
+
+    let mut new_history = vec![];
+
+    for updated in discussions {
+        let (at, id) = (updated.last_update, updated.id.clone());
+        match state.discussions.entry(updated.id.clone()) {
             Entry::Occupied(mut e) => {
                 let stored = e.get_mut();
                 if stored.is_open != updated.is_open {
-                    new_history.push((updated.last_update, updated.id.clone(), issue_state(false)));
+                    let how = if updated.is_open {
+                        DiscussionAction::New
+                    } else {
+                        DiscussionAction::Closed
+                    };
+                    new_history.push((at, id.clone(), how));
+                }
+                if stored.answer.is_none() && updated.answer.is_some() {
+                    let at = updated.answered_at.unwrap_or(at);
+                    new_history.push((at, id.clone(), DiscussionAction::Answered));
+                }
+                if !stored.is_locked && updated.is_locked {
+                    new_history.push((at, id, DiscussionAction::Locked));
                 }
                 *stored = updated;
             }
             Entry::Vacant(e) => {
-                new_history.push((updated.last_update, updated.id.clone(), issue_state(true)));
+                let how = if updated.is_open {
+                    DiscussionAction::New
+                } else {
+                    DiscussionAction::NewClosed
+                };
+                new_history.push((at, id, how));
                 e.insert(updated);
             }
         }
@@ -202,239 +783,709 @@ fn sync_issues(mut state: State, github: &github::Github) -> Result<Option<State
 
     new_history.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
     if let Some(&(at, _, _)) = new_history.last() {
-        state.issues_updated = Some(at);
+        state.discussions_updated = Some(at);
     }
-    state.issue_history.append(&mut new_history);
+    state.discussion_history.append(&mut new_history);
 
     Ok(Some(state))
 }
 
+/// Settings for which issue changes `sync-issues` reports.
+struct IssueTracking {
+    comments: CommentArgs,
+    edits: EditArgs,
+}
+
+/// Settings for which PR changes `sync-prs` reports.
+struct PullTracking {
+    hold_drafts: bool,
+    poll_checks: bool,
+    comments: CommentArgs,
+    edits: EditArgs,
+}
+
+/// Settings for the optional landing detection stage of `sync-prs`.
+struct LandingDetection<'a> {
+    local_repo: &'a Path,
+    patterns: &'a ChannelPatterns,
+    named_channels: &'a [NamedChannel],
+    detect_reverts: bool,
+}
+
 fn sync_prs(
     mut state: State,
     github: &github::Github,
-    local_repo: impl AsRef<Path>,
-    channel_patterns: &ChannelPatterns,
+    tracking: &PullTracking,
+    landing: Option<&LandingDetection>,
 ) -> Result<Option<State>> {
-    let local_repo = local_repo.as_ref();
-    let prs = github.query_pulls(state.pull_requests_updated)?;
+    let prs = github.query_pulls(state.pull_requests_updated, tracking.comments.window())?;
 
     let mut new_history = vec![];
 
     for updated in prs {
-        let pr_state = |is_new| match (updated.is_open, updated.is_merged, is_new) {
-            (false, false, true) => PullAction::NewClosed,
-            (false, false, false) => PullAction::Closed,
-            (true, false, _) => PullAction::New,
-            (_, true, true) => PullAction::NewMerged,
-            (_, true, false) => PullAction::Merged,
-        };
-        match state.pull_requests.entry(updated.id.clone()) {
-            Entry::Occupied(mut e) => {
-                let stored = e.get_mut();
+        update_pull(&mut state, updated, tracking, &mut new_history);
+    }
+
+    // check results and landings are not dated by github updates and must
+    // not move the github sync cursor.
+    let synced = new_history
+        .iter()
+        .filter(|(_, _, how)| !matches!(how, PullAction::ChecksFailed | PullAction::ChecksPassed))
+        .map(|&(at, _, _)| at)
+        .max();
+    if let Some(at) = synced {
+        state.pull_requests_updated = Some(at);
+    }
+
+    if tracking.poll_checks {
+        for updated in github.query_open_pulls(tracking.comments.window())? {
+            update_pull(&mut state, updated, tracking, &mut new_history);
+        }
+    }
+
+    if let Some(landing) = landing {
+        detect_landings(&mut state, landing, &mut new_history)?;
+        propagate_landings(&mut state, &new_history);
+    }
+
+    new_history.sort_by(|a, b| (a.0, &a.1, &a.2).cmp(&(b.0, &b.1, &b.2)));
+    state.pull_history.append(&mut new_history);
+
+    Ok(Some(state))
+}
+
+fn update_pull(
+    state: &mut State,
+    mut updated: PullRequest,
+    tracking: &PullTracking,
+    new_history: &mut Vec<(DateTime, String, PullAction)>,
+) {
+    let pr_state = |pr: &PullRequest, is_new| match (pr.is_open, pr.is_merged, is_new) {
+        (false, false, true) => PullAction::NewClosed,
+        (false, false, false) => PullAction::Closed,
+        (true, false, _) => PullAction::New,
+        (_, true, true) => PullAction::NewMerged,
+        (_, true, false) => PullAction::Merged,
+    };
+    let held = tracking.hold_drafts && updated.is_open && updated.is_draft;
+    let (at, id) = (updated.last_update, updated.id.clone());
+    match state.pull_requests.entry(updated.id.clone()) {
+        Entry::Occupied(mut e) => {
+            let stored = e.get_mut();
+            let mut events = vec![];
+            if stored.held_as_draft {
+                if !held {
+                    events.push(pr_state(&updated, true));
+                }
+                stored.reported_comments = Some(updated.comment_count);
+            } else {
                 if (stored.is_open, stored.is_merged) != (updated.is_open, updated.is_merged) {
-                    new_history.push((updated.last_update, updated.id.clone(), pr_state(false)));
+                    events.push(pr_state(&updated, false));
+                } else if stored.is_draft && !updated.is_draft && updated.is_open {
+                    events.push(PullAction::ReadyForReview);
+                }
+                if let Some(how) = check_change(stored, &updated) {
+                    new_history.push((Utc::now(), id.clone(), how));
+                    stored.reported_checks = updated.checks;
+                }
+                let new = tracking.comments.new_comments(
+                    &mut stored.reported_comments,
+                    updated.comment_count,
+                    &updated.latest_comments,
+                );
+                events.extend(new.map(PullAction::Commented));
+                let edit = tracking.edits.edit(
+                    (&stored.title, &stored.body),
+                    (&updated.title, &updated.body),
+                    PullAction::Edited,
+                );
+                events.extend(edit);
+                if updated.is_open && stored.review_decision != updated.review_decision {
+                    match updated.review_decision {
+                        Some(ReviewDecision::Approved) => events.push(PullAction::Approved),
+                        Some(ReviewDecision::ChangesRequested) => {
+                            events.push(PullAction::ChangesRequested);
+                        }
+                        _ => (),
+                    }
                 }
-                stored.update(updated);
             }
-            Entry::Vacant(e) => {
-                new_history.push((updated.last_update, updated.id.clone(), pr_state(true)));
-                e.insert(updated);
+            new_history.extend(events.into_iter().map(|how| (at, id.clone(), how)));
+            stored.held_as_draft &= held;
+            stored.update(updated);
+        }
+        Entry::Vacant(e) => {
+            updated.reported_checks = updated.checks.filter(|c| c.is_settled());
+            updated.reported_comments = Some(updated.comment_count);
+            if held {
+                updated.held_as_draft = true;
+            } else {
+                new_history.push((at, id, pr_state(&updated, true)));
             }
+            e.insert(updated);
         }
     }
+}
 
-    let mut git_cmd = process::Command::new("git");
-    let kind = if local_repo.exists() {
-        git_cmd.arg("-C").arg(local_repo).args([
-            "fetch",
-            "--force",
-            "--prune",
-            "origin",
-            "refs/heads/*:refs/heads/*",
-        ]);
-        "fetch"
-    } else {
-        let url = format!("https://github.com/{}/{}", &state.owner, &state.repo);
-        git_cmd
-            .arg("clone")
-            .args([&url, "--filter", "tree:0", "--bare"])
-            .arg(local_repo);
-        "clone"
+/// Returns the check event to report for `updated`. Checks are reported
+/// whenever they settle on a different outcome than last reported, including
+/// the first time they settle after the PR was found with pending checks.
+fn check_change(stored: &PullRequest, updated: &PullRequest) -> Option<PullAction> {
+    let reported_failed = stored.reported_checks.map(CheckState::is_failed);
+    match updated.checks {
+        Some(checks) if !updated.is_open || !checks.is_settled() => None,
+        Some(checks) if Some(checks.is_failed()) == reported_failed => None,
+        Some(checks) if checks.is_failed() => Some(PullAction::ChecksFailed),
+        Some(_) => Some(PullAction::ChecksPassed),
+        None => None,
+    }
+}
+
+fn detect_landings(
+    state: &mut State,
+    landing: &LandingDetection,
+    new_history: &mut Vec<(DateTime, String, PullAction)>,
+) -> Result<()> {
+    let Some(repo) = &state.repo else {
+        // states tracking all repos of an owner keep one mirror per repo.
+        let repos = state
+            .pull_requests
+            .values()
+            .filter_map(|pr| pr.repo.clone())
+            .collect::<BTreeSet<_>>();
+        for repo in repos {
+            let mirror = Mirror {
+                repo: Some(&repo),
+                url: format!("https://github.com/{}/{repo}", state.owner),
+                path: &landing.local_repo.join(&repo),
+            };
+            detect_repo_landings(state, &mirror, landing, new_history)?;
+        }
+        return Ok(());
+    };
+    let mirror = Mirror {
+        repo: None,
+        url: match &state.remote {
+            Some(url) => url.clone(),
+            None => format!("https://github.com/{}/{repo}", state.owner),
+        },
+        path: landing.local_repo,
     };
+    detect_repo_landings(state, &mirror, landing, new_history)
+}
+
+/// A local mirror used for landing detection.
+struct Mirror<'a> {
+    /// The repo whose PRs are checked, or `None` for all PRs.
+    repo: Option<&'a str>,
+    url: String,
+    path: &'a Path,
+}
 
-    let git_status = git_cmd.spawn()?.wait()?;
-    if !git_status.success() {
-        bail!("{kind} failed: {git_status}");
+impl Mirror<'_> {
+    fn has(&self, pr: &PullRequest) -> bool {
+        self.repo
+            .map_or(true, |repo| pr.repo.as_deref() == Some(repo))
     }
+}
 
-    let targets = state
+fn detect_repo_landings(
+    state: &mut State,
+    mirror: &Mirror,
+    landing: &LandingDetection,
+    new_history: &mut Vec<(DateTime, String, PullAction)>,
+) -> Result<()> {
+    let patterns = state
         .pull_requests
         .values()
-        .map(|pr| pr.base_ref.clone())
-        .collect::<BTreeSet<_>>();
-    let patterns = targets
-        .iter()
-        .map(|target| (target.as_str(), channel_patterns.find_channels(target)))
+        .filter(|pr| mirror.has(pr))
+        .map(|pr| {
+            let channel_globs = landing.patterns.find_channels(&state.pull_target(pr));
+            (pr.base_ref.clone(), channel_globs)
+        })
         .filter(|(_, channel_globs)| !channel_globs.is_empty())
         .collect::<BTreeMap<_, _>>();
+    if patterns.is_empty() {
+        return Ok(());
+    }
+
+    let repo = LocalRepo::new(mirror.path);
+    let ChannelTips {
+        old: old_tips,
+        new: new_tips,
+        named: named_tips,
+    } = update_mirror(&repo, &mirror.url, landing, &patterns)?;
+
+    let reverts = if landing.detect_reverts {
+        let channels = state
+            .pull_requests
+            .values()
+            .filter(|pr| mirror.has(pr))
+            .flat_map(|pr| pr.landed_in.iter().cloned())
+            .collect::<BTreeSet<_>>();
+        Reverts::find(&repo, &channels, &old_tips, &new_tips)?
+    } else {
+        Reverts::default()
+    };
 
     for (id, pr) in &mut state.pull_requests {
-        let Some(merge) = pr.merge_commit.as_ref() else {
+        let Some(merge) = pr.merge_commit.clone().filter(|_| mirror.has(pr)) else {
             continue;
         };
+        let merge = &merge;
+
+        let channel_globs = patterns.get(pr.base_ref.as_str());
+        verify_landings(
+            &repo,
+            (id, pr),
+            channel_globs,
+            &new_tips,
+            &reverts,
+            new_history,
+        )?;
+
         let channel_globs = match patterns.get(pr.base_ref.as_str()) {
             Some(channel_globs) if channel_globs != &pr.landed_in => channel_globs,
             _ => continue,
         };
-        let landed = process::Command::new("git")
-            .arg("-C")
-            .arg(local_repo)
-            .args(["branch", "--contains", merge, "--list"])
-            .args(channel_globs)
-            .output()?;
-        let landed = if landed.status.success() {
-            std::str::from_utf8(&landed.stdout)?
-                .split_whitespace()
-                .filter(|&b| !pr.landed_in.contains(b))
-                .map(ToOwned::to_owned)
-                .collect::<Vec<_>>()
-        } else {
-            bail!(
-                "failed to check landing status of {}: {}, {}",
-                id,
-                landed.status,
-                String::from_utf8_lossy(&landed.stderr)
-            );
+        let landed = channels_containing(&repo, merge, channel_globs, &named_tips)
+            .with_context(|| format!("failed to check landing status of {id}"))?
+            .into_iter()
+            .filter(|b| !pr.landed_in.contains(b))
+            .collect::<Vec<_>>();
+        let now = Utc::now();
+        let mut landed_at = BTreeMap::<DateTime, Vec<String>>::new();
+        for channel in landed {
+            let reference = if named_tips.contains_key(&channel) {
+                LocalRepo::named_channel_ref(&channel)
+            } else {
+                format!("refs/heads/{channel}")
+            };
+            let at = repo
+                .landing_time(merge, &reference)?
+                .map_or(now, |at| at.min(now));
+            pr.landed_in.insert(channel.clone());
+            pr.landings_detected.insert(channel.clone(), now);
+            landed_at.entry(at).or_default().push(channel);
+        }
+        for (at, landed) in landed_at {
+            new_history.push((at, id.clone(), PullAction::Landed(landed)));
+        }
+    }
+
+    Ok(())
+}
+
+/// Reverts found in the channels of a mirror.
+#[derive(Default)]
+struct Reverts {
+    /// Commits reverted, with the channels they were reverted in.
+    reverted: BTreeMap<String, BTreeSet<String>>,
+    /// Commits whose revert was reverted, with the channels they were
+    /// restored in.
+    restored: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl Reverts {
+    fn find(
+        repo: &LocalRepo,
+        channels: &BTreeSet<String>,
+        old_tips: &BTreeMap<String, String>,
+        new_tips: &BTreeMap<String, String>,
+    ) -> Result<Self> {
+        let reverted = repo.reverts_in(channels, old_tips, new_tips)?;
+        let mut restored = BTreeMap::<String, BTreeSet<String>>::new();
+        for (commit, channels) in &reverted {
+            if let Some(merge) = repo.reverted_commit(commit)? {
+                restored
+                    .entry(merge)
+                    .or_default()
+                    .extend(channels.iter().cloned());
+            }
+        }
+        Ok(Reverts { reverted, restored })
+    }
+}
+
+/// Verifies the landings of `pr` in channels matching `channel_globs`
+/// against the current `tips`, and applies `reverts` of its merge commit.
+/// Checking every landing catches force-pushed and deleted channels as well
+/// as fresh mirrors.
+fn verify_landings(
+    repo: &LocalRepo,
+    (id, pr): (&String, &mut PullRequest),
+    channel_globs: Option<&BTreeSet<String>>,
+    tips: &BTreeMap<String, String>,
+    reverts: &Reverts,
+    new_history: &mut Vec<(DateTime, String, PullAction)>,
+) -> Result<()> {
+    let Some(merge) = pr.merge_commit.as_deref() else {
+        return Ok(());
+    };
+    let tracked = |channel: &str| {
+        channel_globs.is_some_and(|globs| {
+            globs.iter().any(|glob| {
+                glob.parse::<ChannelGlob>()
+                    .is_ok_and(|g| g.matches(channel))
+            })
+        })
+    };
+
+    let mut unlanded = vec![];
+    for channel in pr.landed_in.iter().filter(|c| tracked(c)) {
+        let landed = match tips.get(channel) {
+            Some(tip) => repo.is_ancestor(merge, tip)?,
+            None => false,
         };
-        if landed.is_empty() {
-            continue;
+        if !landed {
+            unlanded.push(channel.clone());
+        }
+    }
+    if !unlanded.is_empty() {
+        for channel in &unlanded {
+            pr.landed_in.remove(channel);
+            pr.reverted_in.remove(channel);
+            pr.landings_detected.remove(channel);
         }
-        pr.landed_in.extend(landed.iter().cloned());
-        new_history.push((Utc::now(), id.clone(), PullAction::Landed(landed)));
+        new_history.push((Utc::now(), id.clone(), PullAction::Unlanded(unlanded)));
     }
 
-    new_history.sort_by(|a, b| (a.0, &a.1, &a.2).cmp(&(b.0, &b.1, &b.2)));
-    if let Some(&(at, _, _)) = new_history.last() {
-        state.pull_requests_updated = Some(at);
+    if let Some(channels) = reverts.reverted.get(merge) {
+        let reverted = channels
+            .iter()
+            .filter(|&c| pr.landed_in.contains(c) && !pr.reverted_in.contains(c))
+            .cloned()
+            .collect::<Vec<_>>();
+        if !reverted.is_empty() {
+            pr.reverted_in.extend(reverted.iter().cloned());
+            new_history.push((Utc::now(), id.clone(), PullAction::Reverted(reverted)));
+        }
     }
-    state.pull_history.append(&mut new_history);
 
-    Ok(Some(state))
+    if let Some(channels) = reverts.restored.get(merge) {
+        let relanded = channels
+            .iter()
+            .filter(|&c| pr.landed_in.contains(c) && pr.reverted_in.contains(c))
+            .cloned()
+            .collect::<Vec<_>>();
+        if !relanded.is_empty() {
+            for channel in &relanded {
+                pr.reverted_in.remove(channel);
+            }
+            new_history.push((Utc::now(), id.clone(), PullAction::Landed(relanded)));
+        }
+    }
+
+    Ok(())
 }
 
-fn format_history<V, A: Clone, F: Fn(&V, DateTime, &A) -> Item>(
-    items: &BTreeMap<String, V>,
-    history: &[(DateTime, String, A)],
-    age_hours: u32,
-    format_entry: F,
-    // backwards compat of GUIDs requires this. we need either a different ID format
-    // or an id suffix to give landing events unique ids in all cases, and the suffix
-    // is easier for now
-    id_suffix: impl Fn(&A) -> String,
-) -> Vec<Item> {
-    let since = Utc::now() - Duration::hours(age_hours.into());
-
-    history
-        .iter()
-        .rev()
-        .take_while(|(changed, _, _)| changed >= &since)
-        .map(|(changed, id, how)| {
-            let Some(entry) = items.get(id.as_str()) else {
-                panic!("database is corrupted (dangling key {id})")
-            };
-            Item {
-                guid: Some(Guid {
-                    value: format!("{}/{}{}", changed.to_rfc3339(), id, id_suffix(how)),
-                    permalink: false,
+/// Reports landings of PRs in `new_history` as fixes of the tracked issues
+/// they close.
+fn propagate_landings(state: &mut State, new_history: &[(DateTime, String, PullAction)]) {
+    let mut fixed = vec![];
+    for (at, id, how) in new_history {
+        let PullAction::Landed(channels) = how else {
+            continue;
+        };
+        let pr = &state.pull_requests[id];
+        let closes = |issue: &Issue| {
+            issue.closed_by.as_ref().is_some_and(|c| c.url == pr.url)
+                || pr.closes.iter().any(|c| c.url == issue.url)
+        };
+        fixed.extend(
+            state
+                .issues
+                .iter()
+                .filter(|(_, issue)| closes(issue))
+                .map(|(issue, _)| {
+                    let how = IssueAction::FixedIn(pr.url.clone(), channels.clone());
+                    (*at, issue.clone(), how)
                 }),
-                ..format_entry(entry, *changed, how)
+        );
+    }
+    fixed.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+    state.issue_history.append(&mut fixed);
+}
+
+/// Commits of all channels in the local mirror before and after an update.
+struct ChannelTips {
+    old: BTreeMap<String, String>,
+    new: BTreeMap<String, String>,
+    /// Commits the named channels in use resolved to.
+    named: BTreeMap<String, String>,
+}
+
+/// Fetches the target branches and channel branches of `patterns` and
+/// resolves the named channels among them.
+fn update_mirror(
+    repo: &LocalRepo,
+    url: &str,
+    landing: &LandingDetection,
+    patterns: &BTreeMap<String, BTreeSet<String>>,
+) -> Result<ChannelTips> {
+    let channel_globs = patterns.values().flatten().collect::<BTreeSet<_>>();
+    let named_channels = landing
+        .named_channels
+        .iter()
+        .filter(|c| channel_globs.contains(&c.name))
+        .collect::<Vec<_>>();
+    let refspecs = channel_globs
+        .iter()
+        .filter(|&&glob| !named_channels.iter().any(|c| &c.name == glob))
+        .map(|glob| git::glob_refspec(glob))
+        // targets too, so merge commits of unlanded PRs are known locally.
+        .chain(patterns.keys().map(|target| git::glob_refspec(target)))
+        .collect();
+
+    let old_tips = repo.channel_tips()?;
+    repo.fetch(url, &refspecs)?;
+    let mut named_tips = BTreeMap::new();
+    for channel in named_channels {
+        let commit = channel.resolve()?;
+        repo.set_channel(&channel.name, &commit)?;
+        named_tips.insert(channel.name.clone(), commit);
+    }
+    let new_tips = repo.channel_tips()?;
+
+    Ok(ChannelTips {
+        old: old_tips,
+        new: new_tips,
+        named: named_tips,
+    })
+}
+
+/// Returns all channels matched by `channel_globs` that contain `merge`.
+fn channels_containing(
+    repo: &LocalRepo,
+    merge: &str,
+    channel_globs: &BTreeSet<String>,
+    named_tips: &BTreeMap<String, String>,
+) -> Result<BTreeSet<String>> {
+    let (named, branch_globs): (Vec<_>, Vec<_>) = channel_globs
+        .iter()
+        .partition(|&glob| named_tips.contains_key(glob));
+    let mut contained = if branch_globs.is_empty() {
+        BTreeSet::new()
+    } else {
+        repo.branches_containing(merge, branch_globs)?
+    };
+    for name in named {
+        if repo.is_ancestor(merge, &named_tips[name])? {
+            contained.insert(name.clone());
+        }
+    }
+    Ok(contained)
+}
+
+fn check_patterns(state: &State, patterns: &ChannelPatterns) {
+    let targets = state
+        .pull_requests
+        .values()
+        .map(|pr| state.pull_target(pr))
+        .collect::<BTreeSet<_>>();
+    for target in targets {
+        let channel_globs = patterns.find_channels(&target);
+        if channel_globs.is_empty() {
+            println!("{target}: (no channels)");
+        } else {
+            let channel_globs = channel_globs.into_iter().collect::<Vec<_>>();
+            println!("{target}: {}", channel_globs.join(" "));
+        }
+    }
+}
+
+fn emit_prs(cmd: EmitPrsArgs) -> Result<()> {
+    let state = State::from_file(&cmd.common.state_file)?;
+    let options = cmd
+        .common
+        .feed_options::<PullAction>(feed::DEFAULT_PRS_TITLE)?;
+    let location = cmd.common.location()?;
+    if let Some(dir) = cmd.per_channel {
+        let channels = state
+            .pull_history
+            .iter()
+            .filter_map(|(_, _, how)| match how {
+                PullAction::Landed(channels) => Some(channels),
+                _ => None,
+            })
+            .flatten()
+            .collect::<BTreeSet<_>>();
+        for channel in channels {
+            let out = dir.join(format!("{channel}.xml"));
+            if let Some(parent) = out.parent() {
+                fs::create_dir_all(parent)?;
             }
-        })
-        .collect::<Vec<_>>()
-}
-
-fn new_rss_item(tag: &str, title: &str, url: &str, changed: DateTime, body: &str) -> Item {
-    ItemBuilder::default()
-        .title(Some(format!("{tag} {title}")))
-        .link(Some(url.to_string()))
-        .pub_date(Some(changed.to_rfc2822()))
-        .content(Some(body.to_string()))
-        .build()
-}
-
-fn issues_feed(state: &State, age_hours: u32) -> Channel {
-    let entries = format_history(
-        &state.issues,
-        &state.issue_history,
-        age_hours,
-        |issue, changed, how| {
-            let tag = match how {
-                IssueAction::New => "[NEW]",
-                IssueAction::Closed => "[CLOSED]",
-                IssueAction::NewClosed => "[NEW][CLOSED]",
-            };
-            new_rss_item(tag, &issue.title, &issue.url, changed, &issue.body)
-        },
-        |_| String::default(),
-    );
-
-    let channel = ChannelBuilder::default()
-        .title(format!(
-            "Issues labeled `{}' in {}/{}",
-            state.label, state.owner, state.repo
-        ))
-        .items(entries)
-        .build();
-
-    channel
-}
-
-fn prs_feed(state: &State, age_hours: u32) -> Channel {
-    let entries = format_history(
-        &state.pull_requests,
-        &state.pull_history,
-        age_hours,
-        |pr, changed, how| {
-            let (tag, refs) = match how {
-                PullAction::New => ("[NEW]", None),
-                PullAction::NewMerged => ("[NEW][MERGED]", None),
-                PullAction::Closed => ("[CLOSED]", None),
-                PullAction::NewClosed => ("[NEW][CLOSED]", None),
-                PullAction::Merged => ("[MERGED]", None),
-                PullAction::Landed(l) => ("[LANDED]", Some(l.join(" "))),
-            };
-            let info = format!("{}({})", tag, refs.as_ref().unwrap_or(&pr.base_ref));
-            new_rss_item(&info, &pr.title, &pr.url, changed, &pr.body)
-        },
-        |how| match how {
-            PullAction::Landed(chans) => format!("/landed/{}", chans.join("/")),
-            _ => String::default(),
-        },
-    );
+            let location = location.for_channel(channel);
+            let feed = prs_feed(&state, &options, Some(&channel.parse()?), &location)?;
+            write_feed(Some(out), &feed)?;
+        }
+    } else {
+        let feed = prs_feed(&state, &options, cmd.channel.as_ref(), &location)?;
+        write_feed(cmd.common.out, &feed)?;
+    }
+    Ok(())
+}
+
+/// A state the webhook applies events to.
+struct WebhookTarget<'a> {
+    state_file: &'a Path,
+    mirror: PathBuf,
+    owner: String,
+    repo: Option<String>,
+}
+
+impl WebhookTarget<'_> {
+    fn tracks(&self, repo: &webhook::Repo) -> bool {
+        repo.owner().eq_ignore_ascii_case(&self.owner)
+            && (self.repo.as_ref()).map_or(true, |r| r.eq_ignore_ascii_case(&repo.name))
+    }
+}
 
-    let channel = ChannelBuilder::default()
-        .title(format!(
-            "Pull requests labeled `{}' in {}/{}",
-            state.label, state.owner, state.repo
-        ))
-        .items(entries)
-        .build();
+fn webhook(mut cmd: WebhookArgs) -> Result<()> {
+    let secret =
+        env::var("GITHUB_WEBHOOK_SECRET").context("failed to load GITHUB_WEBHOOK_SECRET")?;
+    let patterns = cmd.prs.patterns.load()?;
+    let issues = IssueTracking {
+        comments: cmd.prs.comments,
+        edits: cmd.prs.edits,
+    };
+    let prs = cmd.prs.tracking();
+    // owner and repo of a state never change, so events are routed by the
+    // values read at startup.
+    let targets = cmd
+        .state_files
+        .iter()
+        .map(|state_file| {
+            let state = State::from_file(state_file)
+                .with_context(|| format!("failed to read {}", state_file.display()))?;
+            let mut mirror = state_file.clone().into_os_string();
+            mirror.push(".git");
+            Ok(WebhookTarget {
+                state_file,
+                mirror: mirror.into(),
+                owner: state.owner,
+                repo: state.repo,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
 
-    channel
+    webhook::serve(&cmd.listen, secret.as_bytes(), |event| {
+        let mut applied = false;
+        let mut failed = 0;
+        for target in targets.iter().filter(|t| t.tracks(event.repo())) {
+            let landing = cmd.prs.landing(Some(&target.mirror), &patterns)?;
+            let result = with_state_and_github(target.state_file, |s, g| {
+                let state = apply_event(s, g, &event, &issues, &prs, landing.as_ref())?;
+                applied |= state.is_some();
+                Ok(state)
+            });
+            if let Err(e) = result {
+                error!(
+                    "failed to apply event to {}: {e:?}",
+                    target.state_file.display()
+                );
+                failed += 1;
+            }
+        }
+        if failed > 0 {
+            bail!("failed to apply event to {failed} states");
+        }
+        Ok(applied)
+    })
 }
 
-fn write_feed(to: Option<PathBuf>, channel: &Channel) -> Result<Option<State>> {
-    match to {
-        Some(to) => {
-            let new_file =
-                NamedTempFile::new_in(to.ancestors().nth(1).unwrap_or_else(|| Path::new(".")))?;
+/// Applies a webhook event of a repository tracked by `state` the way a sync
+/// would. Returns `None` if the event does not concern the state.
+fn apply_event(
+    mut state: State,
+    github: &Github,
+    event: &webhook::Event,
+    issues: &IssueTracking,
+    prs: &PullTracking,
+    landing: Option<&LandingDetection>,
+) -> Result<Option<State>> {
+    // events can't tell whether an item matches a search query, so items of
+    // states tracking a query are checked against it with a search.
+    let tracks = |labels: &[String]| match (&state.query, &state.label) {
+        (None, Some(label)) => labels.iter().any(|l| l.eq_ignore_ascii_case(label)),
+        _ => true,
+    };
 
-            channel.write_to(BufWriter::new(&new_file))?;
-            new_file.persist(to)?;
+    match event {
+        webhook::Event::Issue(_, id) => {
+            let issue = github.query_issue(id, issues.comments.window())?;
+            let Some(issue) = issue.filter(|i| tracks(&i.labels)) else {
+                return Ok(None);
+            };
+            if state.query.is_some() && !github.search_matches_issue(&issue)? {
+                return Ok(None);
+            }
+            let mut new_history = vec![];
+            update_issue(&mut state, issue, issues, &mut new_history);
+            new_history.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+            state.issue_history.append(&mut new_history);
+            Ok(Some(state))
+        }
+        webhook::Event::PullRequest(_, id) => {
+            let pr = github.query_pull(id, prs.comments.window())?;
+            let Some(pr) = pr.filter(|pr| tracks(&pr.labels)) else {
+                return Ok(None);
+            };
+            if state.query.is_some() && !github.search_matches_pull(&pr)? {
+                return Ok(None);
+            }
+            let mut new_history = vec![];
+            update_pull(&mut state, pr, prs, &mut new_history);
+            new_history.sort_by(|a, b| (a.0, &a.1, &a.2).cmp(&(b.0, &b.1, &b.2)));
+            state.pull_history.append(&mut new_history);
+            Ok(Some(state))
+        }
+        webhook::Event::LabelRenamed { repo, from, to } => {
+            let renamed = state.query.is_none()
+                && (state.label.as_ref()).is_some_and(|l| from.eq_ignore_ascii_case(l));
+            if !renamed {
+                return Ok(None);
+            }
+            if state.repo.is_none() {
+                // the label is only renamed in one repo of an owner-wide
+                // state, the others keep using the old name.
+                warn!(
+                    "tracked label renamed from {from} to {to} in {}, update the state \
+                     if it was renamed in all repos",
+                    repo.name
+                );
+                return Ok(None);
+            }
+            info!("tracked label renamed from {from} to {to}");
+            state.label = Some(to.clone());
+            Ok(Some(state))
+        }
+        webhook::Event::Push(repo, branch) => {
+            let Some(landing) = landing.filter(|l| pushes_channel(&state, l, &repo.name, branch))
+            else {
+                return Ok(None);
+            };
+            let mut new_history = vec![];
+            detect_landings(&mut state, landing, &mut new_history)?;
+            if new_history.is_empty() {
+                return Ok(None);
+            }
+            propagate_landings(&mut state, &new_history);
+            new_history.sort_by(|a, b| (a.0, &a.1, &a.2).cmp(&(b.0, &b.1, &b.2)));
+            state.pull_history.append(&mut new_history);
+            Ok(Some(state))
         }
-        None => println!("{}", channel.to_string()),
     }
-    Ok(None)
+}
+
+/// Whether a push to `branch` of `repo` can land tracked PRs.
+fn pushes_channel(state: &State, landing: &LandingDetection, repo: &str, branch: &str) -> bool {
+    state
+        .pull_requests
+        .values()
+        .filter(|pr| state.repo.is_some() || pr.repo.as_deref() == Some(repo))
+        .flat_map(|pr| landing.patterns.find_channels(&state.pull_target(pr)))
+        .any(|glob| glob.parse::<ChannelGlob>().is_ok_and(|g| g.matches(branch)))
 }
 
 fn main() -> Result<()> {
@@ -445,39 +1496,95 @@ fn main() -> Result<()> {
             state_file,
             owner,
             repo,
-            label,
+            tracked,
+            remote,
         } => {
             let state = State {
                 version: STATE_VERSION,
                 owner,
-                repo,
-                label,
+                repo: Some(repo),
+                label: tracked.label,
+                query: tracked.query.map(|q| q.0),
+                remote,
                 ..State::default()
             };
-
-            let file = File::options()
-                .create_new(true)
-                .write(true)
-                .open(state_file)?;
-            to_writer(file, &state)?;
+            create_state(state_file, &state)?;
+        }
+        Command::InitOwner {
+            state_file,
+            owner,
+            tracked,
+        } => {
+            let state = State {
+                version: STATE_VERSION,
+                owner,
+                repo: None,
+                label: tracked.label,
+                query: tracked.query.map(|q| q.0),
+                ..State::default()
+            };
+            create_state(state_file, &state)?;
         }
         Command::SyncIssues(cmd) => {
-            with_state_and_github(cmd.state_file, sync_issues)?;
+            let tracking = IssueTracking {
+                comments: cmd.comments,
+                edits: cmd.edits,
+            };
+            with_state_and_github(cmd.state_file, |s, g| sync_issues(s, g, &tracking))?;
         }
-        Command::SyncPrs(cmd) => {
+        Command::SyncPrs(mut cmd) => {
+            let patterns = cmd.prs.patterns.load()?;
+            let landing = cmd.prs.landing(cmd.local_repo.as_deref(), &patterns)?;
+            let tracking = cmd.prs.tracking();
             with_state_and_github(&cmd.state_file, |s, g| {
-                sync_prs(s, g, cmd.local_repo, &cmd.patterns)
+                sync_prs(s, g, &tracking, landing.as_ref())
             })?;
         }
-        Command::EmitIssues(cmd) => {
-            let state = State::from_file(cmd.state_file)?;
-            write_feed(cmd.out, &issues_feed(&state, cmd.age_hours))?;
+        Command::SyncDiscussions { state_file } => {
+            with_state_and_github(state_file, sync_discussions)?;
         }
-        Command::EmitPrs(cmd) => {
+        Command::CheckPatterns(mut cmd) => {
             let state = State::from_file(cmd.state_file)?;
-            write_feed(cmd.out, &prs_feed(&state, cmd.age_hours))?;
+            check_patterns(&state, &cmd.patterns.load()?);
         }
-    };
+        Command::EmitIssues(cmd) => {
+            let state = State::from_file(&cmd.state_file)?;
+            let options = cmd.feed_options::<IssueAction>(feed::DEFAULT_ISSUES_TITLE)?;
+            let location = cmd.location()?;
+            write_feed(cmd.out, &issues_feed(&state, &options, &location)?)?;
+        }
+        Command::EmitDiscussions(cmd) => {
+            let state = State::from_file(&cmd.state_file)?;
+            let options = cmd.feed_options::<DiscussionAction>(feed::DEFAULT_DISCUSSIONS_TITLE)?;
+            let location = cmd.location()?;
+            write_feed(cmd.out, &discussions_feed(&state, &options, &location)?)?;
+        }
+        Command::EmitPrs(cmd) => emit_prs(cmd)?,
+        Command::Webhook(cmd) => webhook(cmd)?,
+        Command::Notify(cmd) => notify(&cmd)?,
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::SearchQuery;
+
+    fn accepts(query: &str) -> bool {
+        query.parse::<SearchQuery>().is_ok()
+    }
+
+    #[test]
+    fn search_query_qualifiers() {
+        assert!(!accepts("is:pr repo:x"));
+        assert!(!accepts("is:pr -org:x"));
+        assert!(!accepts("user:x"));
+        assert!(!accepts("is:pr Sort:updated"));
+        assert!(accepts(r#"is:pr "repo:x""#));
+        assert!(accepts(r#"is:pr "docs repo:x""#));
+        assert!(accepts(r#"label:"a b" is:open"#));
+        assert!(!accepts(r#"label:"a b" repo:x"#));
+        assert!(accepts("is:pr author:app/dependabot"));
+    }
+}
@@ -0,0 +1,442 @@
+use std::{collections::BTreeMap, env, fs::File, io::BufReader, path::Path};
+
+use anyhow::{Context, Result};
+use lettre::{
+    message::header::ContentType, transport::smtp::authentication::Credentials, Message,
+    SmtpTransport, Transport,
+};
+use reqwest::blocking::Client;
+use rss::Item;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    feed::{issue_notifications, pull_notifications, FeedOptions},
+    state::{Action, DateTime, Delivered, State},
+};
+
+/// Somewhere new history entries are delivered to.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Sink {
+    /// POSTs each entry as a JSON object.
+    Json { url: String },
+    /// POSTs messages to a slack incoming webhook.
+    Slack { url: String },
+    /// POSTs messages to a matrix hookshot generic webhook.
+    Matrix { url: String },
+    /// Mails each entry through an SMTP server, using STARTTLS. The password
+    /// is read from `SMTP_PASSWORD`.
+    Email {
+        server: String,
+        #[serde(default)]
+        port: Option<u16>,
+        #[serde(default)]
+        username: Option<String>,
+        from: String,
+        to: Vec<String>,
+    },
+}
+
+/// A history entry, rendered like a feed item.
+struct Notification<'a> {
+    /// `issue` or `pull_request`.
+    kind: &'static str,
+    id: &'a str,
+    changed: DateTime,
+    action: &'static str,
+    item: &'a Item,
+}
+
+#[derive(Serialize)]
+struct JsonPayload<'a> {
+    kind: &'a str,
+    id: &'a str,
+    changed: String,
+    action: &'a str,
+    title: Option<&'a str>,
+    url: Option<&'a str>,
+    content: Option<&'a str>,
+}
+
+/// Reads a JSON file mapping sink names to sinks.
+pub fn load_sinks(file: impl AsRef<Path>) -> Result<BTreeMap<String, Sink>> {
+    let file = file.as_ref();
+    let reader = BufReader::new(
+        File::open(file).with_context(|| format!("failed to open {}", file.display()))?,
+    );
+    serde_json::from_reader(reader).with_context(|| format!("failed to read {}", file.display()))
+}
+
+/// Escapes the control characters of slack's message formatting.
+fn escape_slack(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Escapes text for html contents and attribute values.
+fn escape(s: &str) -> String {
+    escape_slack(s).replace('"', "&quot;")
+}
+
+impl Sink {
+    fn deliver(&self, http: &Client, n: &Notification) -> Result<()> {
+        let title = n.item.title().unwrap_or(n.id);
+        let url = n.item.link().unwrap_or_default();
+        let post = |url: &str, payload: &serde_json::Value| -> Result<()> {
+            http.post(url).json(payload).send()?.error_for_status()?;
+            Ok(())
+        };
+        match self {
+            Sink::Json { url: to } => post(
+                to,
+                &serde_json::to_value(JsonPayload {
+                    kind: n.kind,
+                    id: n.id,
+                    changed: n.changed.to_rfc3339(),
+                    action: n.action,
+                    title: n.item.title(),
+                    url: n.item.link(),
+                    content: n.item.content(),
+                })?,
+            ),
+            Sink::Slack { url: to } => post(
+                to,
+                &json!({ "text": format!("<{}|{}>", escape_slack(url), escape_slack(title)) }),
+            ),
+            Sink::Matrix { url: to } => post(
+                to,
+                &json!({
+                    "text": format!("{title} {url}"),
+                    "html": format!("<a href=\"{}\">{}</a>", escape(url), escape(title)),
+                }),
+            ),
+            Sink::Email {
+                server,
+                port,
+                username,
+                from,
+                to,
+            } => {
+                let mut message = Message::builder().from(from.parse()?).subject(title);
+                for to in to {
+                    message = message.to(to.parse()?);
+                }
+                let message = message.header(ContentType::TEXT_HTML).body(format!(
+                    "{}<p><a href=\"{}\">{}</a></p>",
+                    n.item.content().unwrap_or_default(),
+                    escape(url),
+                    escape(url),
+                ))?;
+                let mut transport = SmtpTransport::starttls_relay(server)?;
+                if let Some(port) = port {
+                    transport = transport.port(*port);
+                }
+                if let Some(username) = username {
+                    let password =
+                        env::var("SMTP_PASSWORD").context("failed to load SMTP_PASSWORD")?;
+                    transport = transport.credentials(Credentials::new(username.clone(), password));
+                }
+                transport.build().send(&message)?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Delivers the history entries of `state` past `delivered`, oldest
+    /// first, and counts them in `delivered` as they go out. Stops at the
+    /// first failed delivery, which is retried next time.
+    fn deliver_new(
+        &self,
+        name: &str,
+        http: &Client,
+        state: &State,
+        options: &FeedOptions,
+        delivered: &mut Delivered,
+    ) -> Result<()> {
+        let history = state
+            .issue_history
+            .get(delivered.issues..)
+            .unwrap_or_else(|| {
+                warn!(
+                    "{name} got {} issue history entries, but there are only {}. \
+                     skipping to the end",
+                    delivered.issues,
+                    state.issue_history.len()
+                );
+                delivered.issues = state.issue_history.len();
+                &[]
+            });
+        let items = issue_notifications(state, options, history)?;
+        for ((changed, id, how), item) in history.iter().zip(&items) {
+            if let Some(item) = item {
+                self.deliver(
+                    http,
+                    &Notification {
+                        kind: "issue",
+                        id,
+                        changed: *changed,
+                        action: how.kind(),
+                        item,
+                    },
+                )?;
+            }
+            delivered.issues += 1;
+        }
+
+        let history = state
+            .pull_history
+            .get(delivered.pulls..)
+            .unwrap_or_else(|| {
+                warn!(
+                    "{name} got {} PR history entries, but there are only {}. \
+                     skipping to the end",
+                    delivered.pulls,
+                    state.pull_history.len()
+                );
+                delivered.pulls = state.pull_history.len();
+                &[]
+            });
+        let items = pull_notifications(state, options, history)?;
+        for ((changed, id, how), item) in history.iter().zip(&items) {
+            if let Some(item) = item {
+                self.deliver(
+                    http,
+                    &Notification {
+                        kind: "pull_request",
+                        id,
+                        changed: *changed,
+                        action: how.kind(),
+                        item,
+                    },
+                )?;
+            }
+            delivered.pulls += 1;
+        }
+
+        Ok(())
+    }
+}
+
+/// Delivers new history of `state` to all `sinks` and returns how many of
+/// them failed. Sinks not seen before start at the end of the history instead
+/// of replaying it. `save` is called after each sink, so entries delivered
+/// are not sent again if a later sink fails.
+pub fn deliver_all(
+    http: &Client,
+    sinks: &BTreeMap<String, Sink>,
+    state: &mut State,
+    options: &FeedOptions,
+    mut save: impl FnMut(&State) -> Result<()>,
+) -> Result<usize> {
+    let mut failed = 0;
+    for (name, sink) in sinks {
+        let mut delivered = state.notified.get(name).copied().unwrap_or(Delivered {
+            issues: state.issue_history.len(),
+            pulls: state.pull_history.len(),
+        });
+        let result = sink.deliver_new(name, http, state, options, &mut delivered);
+        state.notified.insert(name.clone(), delivered);
+        save(state)?;
+        if let Err(e) = result {
+            error!("failed to notify {name}: {e:?}");
+            failed += 1;
+        }
+    }
+    Ok(failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeMap, VecDeque},
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    use reqwest::blocking::Client;
+    use serde_json::{json, Value};
+    use tiny_http::{Response, Server};
+
+    use super::{deliver_all, escape, escape_slack, Sink};
+    use crate::{
+        feed::{self, ActionFilter, FeedOptions, ItemFilter, Templates},
+        state::{Delivered, Issue, IssueAction, State},
+    };
+
+    /// A JSON sink that answers with queued status codes, 200 once they run
+    /// out, and records the ids of all entries it was sent.
+    struct Receiver {
+        url: String,
+        statuses: Arc<Mutex<VecDeque<u16>>>,
+        received: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Receiver {
+        fn start() -> Self {
+            let server = Server::http("127.0.0.1:0").unwrap();
+            let url = format!("http://{}", server.server_addr());
+            let statuses = Arc::new(Mutex::new(VecDeque::new()));
+            let received = Arc::new(Mutex::new(vec![]));
+            let (s, r) = (statuses.clone(), received.clone());
+            thread::spawn(move || {
+                for mut request in server.incoming_requests() {
+                    let mut body = String::new();
+                    request.as_reader().read_to_string(&mut body).unwrap();
+                    let status = s.lock().unwrap().pop_front().unwrap_or(200);
+                    if status == 200 {
+                        let body: Value = serde_json::from_str(&body).unwrap();
+                        r.lock()
+                            .unwrap()
+                            .push(body["id"].as_str().unwrap().to_owned());
+                    }
+                    let _ = request.respond(Response::empty(status));
+                }
+            });
+            Receiver {
+                url,
+                statuses,
+                received,
+            }
+        }
+
+        fn sinks(&self) -> BTreeMap<String, Sink> {
+            BTreeMap::from([(
+                "json".to_owned(),
+                Sink::Json {
+                    url: self.url.clone(),
+                },
+            )])
+        }
+
+        fn take(&self) -> Vec<String> {
+            std::mem::take(&mut self.received.lock().unwrap())
+        }
+    }
+
+    fn options() -> FeedOptions {
+        FeedOptions {
+            age_hours: 0,
+            actions: ActionFilter::new::<IssueAction>(&[], &[]).unwrap(),
+            templates: Templates::new(
+                feed::DEFAULT_ISSUES_TITLE,
+                feed::DEFAULT_ITEM_TITLE,
+                feed::DEFAULT_ITEM_CONTENT,
+            )
+            .unwrap(),
+            sanitize: true,
+            max_body_length: None,
+            max_items: None,
+            filter: ItemFilter::default(),
+            ttl: None,
+        }
+    }
+
+    /// A state with issues `1` to `count`, each reported as new.
+    fn state(count: u32) -> State {
+        let mut state: State = serde_json::from_value(json!({
+            "version": 1,
+            "owner": "o",
+            "repo": "r",
+            "label": "l",
+            "issues_updated": null,
+            "issues": {},
+            "issue_history": [],
+            "pull_requests_updated": null,
+            "pull_requests": {},
+            "pull_history": [],
+        }))
+        .unwrap();
+        for n in 1..=count {
+            add_issue(&mut state, n);
+        }
+        state
+    }
+
+    fn add_issue(state: &mut State, number: u32) {
+        let id = number.to_string();
+        let mut issue: Issue = serde_json::from_value(json!({
+            "title": format!("issue {number}"),
+            "is_open": true,
+            "body": "",
+            "last_update": "2024-01-01T00:00:00Z",
+            "url": format!("https://github.com/o/r/issues/{number}"),
+        }))
+        .unwrap();
+        issue.id = id.clone();
+        state
+            .issue_history
+            .push((issue.last_update, id.clone(), IssueAction::New));
+        state.issues.insert(id, issue);
+    }
+
+    fn deliver(receiver: &Receiver, state: &mut State) -> usize {
+        deliver_all(&Client::new(), &receiver.sinks(), state, &options(), |_| {
+            Ok(())
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn escape_for_slack_and_html() {
+        let title = r#"Revert "<a> & <b>""#;
+        assert_eq!(escape_slack(title), r#"Revert "&lt;a&gt; &amp; &lt;b&gt;""#);
+        assert_eq!(
+            escape(title),
+            "Revert &quot;&lt;a&gt; &amp; &lt;b&gt;&quot;"
+        );
+    }
+
+    #[test]
+    fn new_sinks_skip_old_history() {
+        let receiver = Receiver::start();
+        let mut state = state(2);
+
+        assert_eq!(deliver(&receiver, &mut state), 0);
+        assert!(receiver.take().is_empty());
+        assert_eq!(state.notified["json"].issues, 2);
+
+        add_issue(&mut state, 3);
+        assert_eq!(deliver(&receiver, &mut state), 0);
+        assert_eq!(receiver.take(), ["3"]);
+    }
+
+    #[test]
+    fn failed_sinks_resume_at_failed_entry() {
+        let receiver = Receiver::start();
+        let mut state = state(3);
+        state
+            .notified
+            .insert("json".to_owned(), Delivered::default());
+
+        receiver.statuses.lock().unwrap().extend([200, 500]);
+        assert_eq!(deliver(&receiver, &mut state), 1);
+        assert_eq!(receiver.take(), ["1"]);
+        assert_eq!(state.notified["json"].issues, 1);
+
+        assert_eq!(deliver(&receiver, &mut state), 0);
+        assert_eq!(receiver.take(), ["2", "3"]);
+        assert_eq!(state.notified["json"].issues, 3);
+    }
+
+    #[test]
+    fn sinks_past_the_history_skip_to_its_end() {
+        let receiver = Receiver::start();
+        let mut state = state(1);
+        let delivered = Delivered {
+            issues: 5,
+            pulls: 5,
+        };
+        state.notified.insert("json".to_owned(), delivered);
+
+        assert_eq!(deliver(&receiver, &mut state), 0);
+        assert!(receiver.take().is_empty());
+        assert_eq!(state.notified["json"].issues, 1);
+        assert_eq!(state.notified["json"].pulls, 0);
+
+        add_issue(&mut state, 2);
+        assert_eq!(deliver(&receiver, &mut state), 0);
+        assert_eq!(receiver.take(), ["2"]);
+    }
+}
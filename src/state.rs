@@ -23,14 +23,31 @@ pub const STATE_VERSION: u32 = 1;
 pub struct State {
     pub version: u32,
     pub owner: String,
-    pub repo: String,
-    pub label: String,
+    /// Repository to track, or `None` to track all repositories of `owner`.
+    pub repo: Option<String>,
+    /// Label to track, unless `query` is given.
+    pub label: Option<String>,
+    /// Search query selecting the items to track instead of a label.
+    #[serde(default)]
+    pub query: Option<String>,
+    /// Git URL used for landing detection instead of the github repo.
+    #[serde(default)]
+    pub remote: Option<String>,
     pub issues_updated: Option<DateTime>,
     pub issues: BTreeMap<String, Issue>,
     pub issue_history: Vec<(DateTime, String, IssueAction)>,
     pub pull_requests_updated: Option<DateTime>,
     pub pull_requests: BTreeMap<String, PullRequest>,
     pub pull_history: Vec<(DateTime, String, PullAction)>,
+    #[serde(default)]
+    pub discussions_updated: Option<DateTime>,
+    #[serde(default)]
+    pub discussions: BTreeMap<String, Discussion>,
+    #[serde(default)]
+    pub discussion_history: Vec<(DateTime, String, DiscussionAction)>,
+    /// History delivered to each notification sink, by sink name.
+    #[serde(default)]
+    pub notified: BTreeMap<String, Delivered>,
 }
 
 impl State {
@@ -45,6 +62,28 @@ impl State {
         }
         Ok(state)
     }
+    /// Search qualifiers selecting the tracked items.
+    pub fn search_filter(&self) -> String {
+        let scope = match &self.repo {
+            Some(repo) => format!("repo:{}/{repo}", self.owner),
+            // `user:` covers organizations as well.
+            None => format!("user:{}", self.owner),
+        };
+        match (&self.query, &self.label) {
+            (Some(query), _) => format!("{scope} {query}"),
+            (None, Some(label)) => format!("{scope} label:\"{label}\""),
+            (None, None) => scope,
+        }
+    }
+
+    /// What channel patterns match the target of `pr` against: its base
+    /// branch, as `repo/branch` in states tracking all repos of the owner.
+    pub fn pull_target(&self, pr: &PullRequest) -> String {
+        match (&self.repo, &pr.repo) {
+            (None, Some(repo)) => format!("{repo}/{}", pr.base_ref),
+            _ => pr.base_ref.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -57,16 +96,161 @@ pub struct Issue {
     pub body: String,
     pub last_update: DateTime,
     pub url: String,
+    /// Name of the repository, without the owner.
+    #[serde(default)]
+    pub repo: Option<String>,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub assignees: Vec<String>,
+    #[serde(default)]
+    pub milestone: Option<String>,
+    #[serde(default)]
+    pub labels: Vec<String>,
+    #[serde(default)]
+    pub created_at: Option<DateTime>,
+    #[serde(default)]
+    pub closed_at: Option<DateTime>,
+    #[serde(default)]
+    pub comment_count: u64,
+    /// The most recent comments, if comments are tracked.
+    #[serde(default)]
+    pub latest_comments: Vec<Comment>,
+    /// Comment count last reported in history, or seen when the item was
+    /// first found.
+    #[serde(default)]
+    pub reported_comments: Option<u64>,
+    /// The PR that closed the issue, if it was closed by one.
+    #[serde(default)]
+    pub closed_by: Option<ItemRef>,
+}
+
+/// Number of history entries delivered to a notification sink. Histories
+/// are only ever appended to, so entries past these are undelivered.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Delivered {
+    pub issues: usize,
+    pub pulls: usize,
+}
+
+/// An issue or PR, which need not be tracked.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ItemRef {
+    pub number: i64,
+    pub title: String,
+    pub url: String,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Comment {
+    pub author: Option<String>,
+    /// Whether the author is an owner, member or collaborator of the repo.
+    pub by_maintainer: bool,
+    pub body: String,
+    pub created_at: DateTime,
+    pub url: String,
+}
+
+/// A kind of change recorded in a history.
+pub trait Action {
+    /// Names of all kinds of actions, as accepted by feed filters.
+    const KINDS: &'static [&'static str];
+
+    fn kind(&self) -> &'static str;
+
+    /// Channels affected by landing events, `None` for all other actions.
+    fn channels(&self) -> Option<&[String]> {
+        None
+    }
+
+    /// Number of comments summarized by comment events.
+    fn new_comments(&self) -> Option<u64> {
+        None
+    }
+
+    /// Title before a renaming edit.
+    fn previous_title(&self) -> Option<&str> {
+        None
+    }
+
+    /// URL of the PR that closed or fixed an issue.
+    fn closer(&self) -> Option<&str> {
+        None
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub enum IssueAction {
     New,
     Closed,
     NewClosed,
+    Commented(u64),
+    /// The title or body changed, with the previous title if it changed.
+    Edited(Option<String>),
+    /// Closed by merging the PR with the given url. Follows the `Closed` or
+    /// `NewClosed` entry of the close.
+    ClosedBy(String),
+    /// A PR closing the issue landed in the given channels.
+    FixedIn(String, Vec<String>),
 }
 
+impl Action for IssueAction {
+    const KINDS: &'static [&'static str] = &[
+        "New",
+        "Closed",
+        "NewClosed",
+        "Commented",
+        "Edited",
+        "ClosedBy",
+        "FixedIn",
+    ];
+
+    fn kind(&self) -> &'static str {
+        match self {
+            IssueAction::New => "New",
+            IssueAction::Closed => "Closed",
+            IssueAction::NewClosed => "NewClosed",
+            IssueAction::Commented(_) => "Commented",
+            IssueAction::Edited(_) => "Edited",
+            IssueAction::ClosedBy(_) => "ClosedBy",
+            IssueAction::FixedIn(..) => "FixedIn",
+        }
+    }
+
+    fn channels(&self) -> Option<&[String]> {
+        match self {
+            IssueAction::FixedIn(_, c) => Some(c),
+            _ => None,
+        }
+    }
+
+    fn new_comments(&self) -> Option<u64> {
+        match self {
+            IssueAction::Commented(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    fn previous_title(&self) -> Option<&str> {
+        match self {
+            IssueAction::Edited(title) => title.as_deref(),
+            _ => None,
+        }
+    }
+
+    fn closer(&self) -> Option<&str> {
+        match self {
+            IssueAction::ClosedBy(pr) | IssueAction::FixedIn(pr, _) => Some(pr),
+            _ => None,
+        }
+    }
+}
+
+#[allow(clippy::struct_excessive_bools)]
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct PullRequest {
@@ -78,23 +262,126 @@ pub struct PullRequest {
     pub body: String,
     pub last_update: DateTime,
     pub url: String,
+    /// Name of the repository, without the owner.
+    #[serde(default)]
+    pub repo: Option<String>,
     pub base_ref: String,
     pub merge_commit: Option<String>,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub assignees: Vec<String>,
+    #[serde(default)]
+    pub milestone: Option<String>,
+    #[serde(default)]
+    pub labels: Vec<String>,
+    #[serde(default)]
+    pub created_at: Option<DateTime>,
+    #[serde(default)]
+    pub closed_at: Option<DateTime>,
+    #[serde(default)]
+    pub merged_at: Option<DateTime>,
+    #[serde(default)]
+    pub head_ref: String,
+    #[serde(default)]
+    pub is_draft: bool,
+    /// Issues the PR closes when merged.
+    #[serde(default)]
+    pub closes: Vec<ItemRef>,
+    #[serde(default)]
+    pub comment_count: u64,
+    /// The most recent comments, if comments are tracked.
+    #[serde(default)]
+    pub latest_comments: Vec<Comment>,
+    #[serde(default)]
+    pub review_decision: Option<ReviewDecision>,
+    /// The latest review of each reviewer.
+    #[serde(default)]
+    pub latest_reviews: Vec<Review>,
+    /// Combined state of the CI checks on the head commit.
+    #[serde(default)]
+    pub checks: Option<CheckState>,
 
     // non-github fields
     #[serde(default)]
     pub landed_in: BTreeSet<String>,
+    #[serde(default)]
+    pub reverted_in: BTreeSet<String>,
+    /// When each channel in `landed_in` was found to contain the PR. The
+    /// `Landed` history entries carry the time the landing actually happened.
+    #[serde(default)]
+    pub landings_detected: BTreeMap<String, DateTime>,
+    /// Whether the PR was not reported as new yet because it is a draft.
+    #[serde(default)]
+    pub held_as_draft: bool,
+    /// The settled check state last reported in history, or seen when the
+    /// PR was first found.
+    #[serde(default)]
+    pub reported_checks: Option<CheckState>,
+    /// Comment count last reported in history, or seen when the PR was
+    /// first found.
+    #[serde(default)]
+    pub reported_comments: Option<u64>,
 }
 
 impl PullRequest {
     pub fn update(&mut self, from: PullRequest) {
         *self = PullRequest {
             landed_in: std::mem::take(&mut self.landed_in),
+            reverted_in: std::mem::take(&mut self.reverted_in),
+            landings_detected: std::mem::take(&mut self.landings_detected),
+            held_as_draft: self.held_as_draft,
+            reported_checks: self.reported_checks,
+            reported_comments: self.reported_comments,
             ..from
         }
     }
 }
 
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum ReviewDecision {
+    Approved,
+    ChangesRequested,
+    ReviewRequired,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum ReviewState {
+    Approved,
+    ChangesRequested,
+    Commented,
+    Dismissed,
+    Pending,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum CheckState {
+    Error,
+    Expected,
+    Failure,
+    Pending,
+    Success,
+}
+
+impl CheckState {
+    pub fn is_failed(self) -> bool {
+        matches!(self, CheckState::Error | CheckState::Failure)
+    }
+
+    /// Whether all checks have finished.
+    pub fn is_settled(self) -> bool {
+        self.is_failed() || self == CheckState::Success
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Review {
+    pub author: Option<String>,
+    pub state: ReviewState,
+    pub submitted_at: Option<DateTime>,
+}
+
 #[derive(Debug, PartialOrd, Ord, PartialEq, Eq, Clone, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub enum PullAction {
@@ -103,5 +390,123 @@ pub enum PullAction {
     NewClosed,
     Merged,
     NewMerged,
+    ReadyForReview,
+    Approved,
+    ChangesRequested,
+    ChecksFailed,
+    ChecksPassed,
+    Commented(u64),
+    /// The title or body changed, with the previous title if it changed.
+    Edited(Option<String>),
     Landed(Vec<String>),
+    Unlanded(Vec<String>),
+    Reverted(Vec<String>),
+}
+
+impl Action for PullAction {
+    const KINDS: &'static [&'static str] = &[
+        "New",
+        "Closed",
+        "NewClosed",
+        "Merged",
+        "NewMerged",
+        "ReadyForReview",
+        "Approved",
+        "ChangesRequested",
+        "ChecksFailed",
+        "ChecksPassed",
+        "Commented",
+        "Edited",
+        "Landed",
+        "Unlanded",
+        "Reverted",
+    ];
+
+    fn kind(&self) -> &'static str {
+        match self {
+            PullAction::New => "New",
+            PullAction::Closed => "Closed",
+            PullAction::NewClosed => "NewClosed",
+            PullAction::Merged => "Merged",
+            PullAction::NewMerged => "NewMerged",
+            PullAction::ReadyForReview => "ReadyForReview",
+            PullAction::Approved => "Approved",
+            PullAction::ChangesRequested => "ChangesRequested",
+            PullAction::ChecksFailed => "ChecksFailed",
+            PullAction::ChecksPassed => "ChecksPassed",
+            PullAction::Commented(_) => "Commented",
+            PullAction::Edited(_) => "Edited",
+            PullAction::Landed(_) => "Landed",
+            PullAction::Unlanded(_) => "Unlanded",
+            PullAction::Reverted(_) => "Reverted",
+        }
+    }
+
+    fn channels(&self) -> Option<&[String]> {
+        match self {
+            PullAction::Landed(c) | PullAction::Unlanded(c) | PullAction::Reverted(c) => Some(c),
+            _ => None,
+        }
+    }
+
+    fn new_comments(&self) -> Option<u64> {
+        match self {
+            PullAction::Commented(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    fn previous_title(&self) -> Option<&str> {
+        match self {
+            PullAction::Edited(title) => title.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Discussion {
+    #[serde(skip)]
+    pub id: String,
+    pub title: String,
+    pub is_open: bool,
+    pub is_locked: bool,
+    pub body: String,
+    pub last_update: DateTime,
+    pub url: String,
+    #[serde(default)]
+    pub repo: Option<String>,
+    pub author: Option<String>,
+    pub category: String,
+    pub labels: Vec<String>,
+    pub created_at: DateTime,
+    pub closed_at: Option<DateTime>,
+    /// The comment chosen as answer.
+    pub answer: Option<Comment>,
+    pub answered_at: Option<DateTime>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum DiscussionAction {
+    New,
+    NewClosed,
+    Answered,
+    Closed,
+    Locked,
+}
+
+impl Action for DiscussionAction {
+    const KINDS: &'static [&'static str] = &["New", "NewClosed", "Answered", "Closed", "Locked"];
+
+    fn kind(&self) -> &'static str {
+        match self {
+            DiscussionAction::New => "New",
+            DiscussionAction::NewClosed => "NewClosed",
+            DiscussionAction::Answered => "Answered",
+            DiscussionAction::Closed => "Closed",
+            DiscussionAction::Locked => "Locked",
+        }
+    }
 }
@@ -0,0 +1,305 @@
+use std::{io::Read, sync::mpsc, thread};
+
+use anyhow::{anyhow, Result};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use tiny_http::{Method, Request, Response, Server};
+
+/// Largest payload github delivers.
+const MAX_PAYLOAD: u64 = 25 << 20;
+
+/// A repository an event happened in.
+#[derive(Debug, Deserialize)]
+pub struct Repo {
+    pub name: String,
+    owner: Owner,
+}
+
+#[derive(Debug, Deserialize)]
+struct Owner {
+    login: String,
+}
+
+impl Repo {
+    pub fn owner(&self) -> &str {
+        &self.owner.login
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Node {
+    node_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct IssuesPayload {
+    issue: Node,
+    repository: Repo,
+}
+
+#[derive(Debug, Deserialize)]
+struct PullRequestPayload {
+    pull_request: Node,
+    repository: Repo,
+}
+
+#[derive(Debug, Deserialize)]
+struct Label {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Change {
+    from: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct LabelChanges {
+    name: Option<Change>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LabelPayload {
+    action: String,
+    label: Label,
+    changes: Option<LabelChanges>,
+    repository: Repo,
+}
+
+#[derive(Debug, Deserialize)]
+struct PushPayload {
+    #[serde(rename = "ref")]
+    reference: String,
+    repository: Repo,
+}
+
+/// The parts of a webhook event that matter for tracking.
+#[derive(Debug)]
+pub enum Event {
+    /// The issue with the given node id changed.
+    Issue(Repo, String),
+    /// The PR with the given node id changed.
+    PullRequest(Repo, String),
+    LabelRenamed {
+        repo: Repo,
+        from: String,
+        to: String,
+    },
+    /// A branch was pushed to.
+    Push(Repo, String),
+}
+
+impl Event {
+    /// Parses a payload of kind `event`, as given in the `X-GitHub-Event`
+    /// header. Returns `None` for events that don't affect tracking.
+    fn parse(event: &str, body: &[u8]) -> Result<Option<Self>> {
+        Ok(match event {
+            "issues" => {
+                let p: IssuesPayload = serde_json::from_slice(body)?;
+                Some(Event::Issue(p.repository, p.issue.node_id))
+            }
+            "pull_request" => {
+                let p: PullRequestPayload = serde_json::from_slice(body)?;
+                Some(Event::PullRequest(p.repository, p.pull_request.node_id))
+            }
+            "label" => {
+                let p: LabelPayload = serde_json::from_slice(body)?;
+                match p.changes.and_then(|c| c.name) {
+                    Some(name) if p.action == "edited" => Some(Event::LabelRenamed {
+                        repo: p.repository,
+                        from: name.from,
+                        to: p.label.name,
+                    }),
+                    _ => None,
+                }
+            }
+            "push" => {
+                let p: PushPayload = serde_json::from_slice(body)?;
+                p.reference
+                    .strip_prefix("refs/heads/")
+                    .map(|branch| Event::Push(p.repository, branch.to_owned()))
+            }
+            _ => None,
+        })
+    }
+
+    pub fn repo(&self) -> &Repo {
+        match self {
+            Event::Issue(repo, _)
+            | Event::PullRequest(repo, _)
+            | Event::LabelRenamed { repo, .. }
+            | Event::Push(repo, _) => repo,
+        }
+    }
+}
+
+/// Checks the `X-Hub-Signature-256` header of a delivery.
+fn verify(secret: &[u8], body: &[u8], signature: Option<&str>) -> bool {
+    let Some(signature) = signature
+        .and_then(|s| s.strip_prefix("sha256="))
+        .and_then(|s| hex::decode(s).ok())
+    else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(secret) else {
+        return false;
+    };
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+fn header<'a>(request: &'a Request, name: &'static str) -> Option<&'a str> {
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv(name))
+        .map(|h| h.value.as_str())
+}
+
+/// Receives webhook deliveries signed with `secret` on `addr` and passes
+/// their events to `handle`, which returns whether it applied the event.
+///
+/// Deliveries are answered once they are verified and events are handled in
+/// order on a separate thread, since github gives up on deliveries that take
+/// longer than 10 seconds to answer.
+pub fn serve(
+    addr: &str,
+    secret: &[u8],
+    mut handle: impl FnMut(Event) -> Result<bool> + Send,
+) -> Result<()> {
+    let server = Server::http(addr).map_err(|e| anyhow!("failed to listen on {addr}: {e}"))?;
+    info!("listening for webhooks on {addr}");
+
+    let (queue, events) = mpsc::channel::<(String, Event)>();
+    thread::scope(|s| {
+        s.spawn(move || {
+            for (kind, event) in events {
+                match handle(event) {
+                    Ok(true) => info!("applied {kind} event"),
+                    Ok(false) => debug!("ignored {kind} event"),
+                    Err(e) => error!("failed to apply {kind} event: {e:?}"),
+                }
+            }
+        });
+
+        for mut request in server.incoming_requests() {
+            let mut body = vec![];
+            let status = if *request.method() != Method::Post {
+                405
+            } else if let Err(e) = request.as_reader().take(MAX_PAYLOAD).read_to_end(&mut body) {
+                warn!("failed to read delivery: {e}");
+                400
+            } else if !verify(secret, &body, header(&request, "X-Hub-Signature-256")) {
+                warn!("rejecting delivery with bad signature");
+                401
+            } else {
+                let kind = header(&request, "X-GitHub-Event").unwrap_or_default();
+                match Event::parse(kind, &body) {
+                    Err(e) => {
+                        warn!("failed to parse {kind} event: {e}");
+                        400
+                    }
+                    Ok(None) => 204,
+                    Ok(Some(event)) => {
+                        if queue.send((kind.to_owned(), event)).is_err() {
+                            bail!("event handler stopped");
+                        }
+                        202
+                    }
+                }
+            };
+            if let Err(e) = request.respond(Response::empty(status)) {
+                warn!("failed to respond to delivery: {e}");
+            }
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+
+    use super::{verify, Event};
+
+    const ISSUES: &[u8] = include_bytes!("../tests/webhook/issues.json");
+    const PULL_REQUEST: &[u8] = include_bytes!("../tests/webhook/pull_request.json");
+    const LABEL: &[u8] = include_bytes!("../tests/webhook/label.json");
+    const PUSH: &[u8] = include_bytes!("../tests/webhook/push.json");
+
+    fn sign(secret: &[u8], body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret).unwrap();
+        mac.update(body);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    #[test]
+    fn verify_signatures() {
+        let signature = sign(b"secret", ISSUES);
+        assert!(verify(
+            b"secret",
+            ISSUES,
+            Some(&format!("sha256={signature}"))
+        ));
+        assert!(!verify(
+            b"other",
+            ISSUES,
+            Some(&format!("sha256={signature}"))
+        ));
+        assert!(!verify(
+            b"secret",
+            PUSH,
+            Some(&format!("sha256={signature}"))
+        ));
+        assert!(!verify(b"secret", ISSUES, Some(&signature)));
+        assert!(!verify(b"secret", ISSUES, Some("sha256=not hex")));
+        assert!(!verify(b"secret", ISSUES, None));
+    }
+
+    fn parse(event: &str, body: &[u8]) -> Option<Event> {
+        Event::parse(event, body).unwrap()
+    }
+
+    #[test]
+    fn parse_events() {
+        let Some(Event::Issue(repo, id)) = parse("issues", ISSUES) else {
+            panic!("issues payload not parsed as issue event");
+        };
+        assert_eq!(
+            (repo.owner(), repo.name.as_str()),
+            ("octo-org", "hello-world")
+        );
+        assert_eq!(id, "I_kwDOKbZx8c5yxh0r");
+
+        let Some(Event::PullRequest(_, id)) = parse("pull_request", PULL_REQUEST) else {
+            panic!("pull_request payload not parsed as PR event");
+        };
+        assert_eq!(id, "PR_kwDOKbZx8c5cQk6b");
+
+        let Some(Event::LabelRenamed { from, to, .. }) = parse("label", LABEL) else {
+            panic!("label payload not parsed as rename");
+        };
+        assert_eq!((from.as_str(), to.as_str()), ("blocker", "release-blocker"));
+
+        let Some(Event::Push(_, branch)) = parse("push", PUSH) else {
+            panic!("push payload not parsed as push event");
+        };
+        assert_eq!(branch, "release-23.11");
+    }
+
+    #[test]
+    fn ignore_irrelevant_events() {
+        let recolored = String::from_utf8(LABEL.to_vec())
+            .unwrap()
+            .replace("\"name\": {\n      \"from\": \"blocker\"\n    }", "");
+        assert!(parse("label", recolored.as_bytes()).is_none());
+        let tag = String::from_utf8(PUSH.to_vec())
+            .unwrap()
+            .replace("refs/heads/release-23.11", "refs/tags/23.11");
+        assert!(parse("push", tag.as_bytes()).is_none());
+        assert!(parse("star", ISSUES).is_none());
+        assert!(Event::parse("issues", PUSH).is_err());
+    }
+}